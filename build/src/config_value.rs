@@ -0,0 +1,227 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+///
+/// A configuration value as written in the build file.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigValue {
+    String(String),
+    // Integers are kept apart from floats so that they are written exactly, even when a float can not hold them.
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    List(Vec<ConfigValue>),
+    Map(BTreeMap<String, ConfigValue>)
+}
+
+impl ConfigValue {
+    ///
+    /// Creates an empty map.
+    ///
+    pub fn new() -> ConfigValue {
+        ConfigValue::Map(BTreeMap::new())
+    }
+
+    ///
+    /// Looks up a value by key. Keys of nested maps are joined with dots so that `conan.options.shared` finds the
+    /// same value whether it was written as a single key or as nested maps.
+    ///
+    pub fn get(&self, key: &str) -> Option<&ConfigValue> {
+        let map = match self {
+            ConfigValue::Map(map) => map,
+            _ => return None
+        };
+
+        if let Some(value) = map.get(key) {
+            return Some(value);
+        }
+
+        // Try every prefix ending at a dot, longest first, as the key of a nested map.
+        for (idx, _) in key.rmatch_indices('.') {
+            if let Some(value) = map.get(&key[..idx]) {
+                if let Some(value) = value.get(&key[idx + 1..]) {
+                    return Some(value);
+                }
+            }
+        }
+
+        None
+    }
+
+    ///
+    /// The value as a string. Numbers and booleans are not converted, use `to_string` for that.
+    ///
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            ConfigValue::String(s) => Some(s),
+            _ => None
+        }
+    }
+
+    ///
+    /// The value as a boolean. The strings "true" and "false" are accepted as well.
+    ///
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            ConfigValue::Bool(b) => Some(*b),
+            ConfigValue::String(s) => s.parse().ok(),
+            _ => None
+        }
+    }
+
+    ///
+    /// The value as a number. Strings containing a number are accepted as well.
+    ///
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            ConfigValue::Integer(n) => Some(*n as f64),
+            ConfigValue::Float(n) => Some(*n),
+            ConfigValue::String(s) => s.parse().ok(),
+            _ => None
+        }
+    }
+
+    ///
+    /// The value as an integer if it is a number without a fractional part. Strings containing an integer are
+    /// accepted as well.
+    ///
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            ConfigValue::Integer(n) => Some(*n),
+            ConfigValue::Float(n) if n.fract() == 0.0 => Some(*n as i64),
+            ConfigValue::String(s) => s.parse().ok(),
+            _ => None
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&Vec<ConfigValue>> {
+        match self {
+            ConfigValue::List(l) => Some(l),
+            _ => None
+        }
+    }
+
+    pub fn as_map(&self) -> Option<&BTreeMap<String, ConfigValue>> {
+        match self {
+            ConfigValue::Map(m) => Some(m),
+            _ => None
+        }
+    }
+
+    ///
+    /// Merges another value into this one. Maps are merged key by key, anything else is replaced.
+    ///
+    pub fn merge(&mut self, other: ConfigValue) {
+        match (self, other) {
+            (ConfigValue::Map(map), ConfigValue::Map(other)) => {
+                for (k, v) in other {
+                    match map.get_mut(&k) {
+                        Some(existing) => existing.merge(v),
+                        None => { map.insert(k, v); }
+                    }
+                }
+            },
+            (this, other) => *this = other
+        }
+    }
+
+    ///
+    /// The flat view of a map: the keys of nested maps are joined with dots and every other value is converted to a
+    /// string, lists being joined with commas.
+    ///
+    pub fn flatten(&self) -> HashMap<String, String> {
+        let mut flat = HashMap::new();
+        if let ConfigValue::Map(map) = self {
+            flatten_into(&mut flat, None, map);
+        }
+        flat
+    }
+}
+
+fn flatten_into(flat: &mut HashMap<String, String>, prefix: Option<&str>, map: &BTreeMap<String, ConfigValue>) {
+    for (k, v) in map {
+        let key = match prefix {
+            Some(prefix) => format!("{}.{}", prefix, k),
+            None => k.clone()
+        };
+        match v {
+            ConfigValue::Map(nested) => flatten_into(flat, Some(&key), nested),
+            v => { flat.insert(key, v.to_string()); }
+        }
+    }
+}
+
+impl Display for ConfigValue {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ConfigValue::String(s) => write!(f, "{}", s),
+            ConfigValue::Integer(n) => write!(f, "{}", n),
+            ConfigValue::Float(n) => write!(f, "{}", n),
+            ConfigValue::Bool(b) => write!(f, "{}", b),
+            ConfigValue::List(l) => {
+                let items : Vec<String> = l.iter().map(|v| v.to_string()).collect();
+                write!(f, "{}", items.join(","))
+            },
+            ConfigValue::Map(m) => {
+                let items : Vec<String> = m.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+                write!(f, "{}", items.join(","))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn map(entries: Vec<(&str, ConfigValue)>) -> ConfigValue {
+        ConfigValue::Map(entries.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+    }
+
+    fn string(s: &str) -> ConfigValue {
+        ConfigValue::String(s.to_string())
+    }
+
+    #[test]
+    fn get_nested() {
+        let config = map(vec!(
+            ("conan.version", ConfigValue::Float(0.1)),
+            ("conan", map(vec!(
+                ("options", map(vec!(("shared", ConfigValue::Bool(true))))),
+            ))),
+            ("cmake.definitions", map(vec!(("FOO", string("bar")))))
+        ));
+
+        assert_eq!(config.get("conan.version").unwrap().as_f64(), Some(0.1));
+        assert_eq!(config.get("conan.options.shared").unwrap().as_bool(), Some(true));
+        assert_eq!(config.get("cmake.definitions.FOO").unwrap().as_str(), Some("bar"));
+        assert!(config.get("conan.options").unwrap().as_map().is_some());
+        assert_eq!(config.get("conan.missing"), None);
+    }
+
+    #[test]
+    fn merge_and_flatten() {
+        let mut config = map(vec!(
+            ("conan", map(vec!(("user", string("a")), ("channel", string("stable"))))),
+            ("conan.requires", ConfigValue::List(vec!(string("x/1@a/b"), string("y/2@a/b"))))
+        ));
+        config.merge(map(vec!(
+            ("conan", map(vec!(("user", string("b"))))),
+            ("count", ConfigValue::Integer(3)),
+            ("id", ConfigValue::Integer(9007199254740993))
+        )));
+
+        let flat = config.flatten();
+        assert_eq!(flat["conan.user"], "b");
+        assert_eq!(flat["conan.channel"], "stable");
+        assert_eq!(flat["conan.requires"], "x/1@a/b,y/2@a/b");
+        assert_eq!(flat["count"], "3");
+        assert_eq!(flat["id"], "9007199254740993");
+        assert_eq!(config.get("count").unwrap().as_i64(), Some(3));
+    }
+}
\ No newline at end of file
@@ -3,9 +3,13 @@ use ::Task;
 use deptree::DepTree;
 use error::BuildError;
 use failure::Error;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
 
+// Prefix of a selector that also selects the same task of every module depending on the matched modules.
+const DEPENDENTS_PREFIX: char = '^';
+
 pub struct Context {
     modules: DepTree<Module>,
     tasks: DepTree<Task>
@@ -37,6 +41,34 @@ impl Context {
         self.tasks.insert_box(name, task)
     }
 
+    pub fn get_module(&self, name: &str) -> Result<&Module, Error> {
+        Ok(self.modules.get_item(name).ok_or(BuildError::NoSuchModule(name.to_string()))?)
+    }
+
+    ///
+    /// The module a task belongs to, based on the "<module>:<task>" naming convention.
+    ///
+    pub fn task_module(&self, task: &str) -> Option<&Module> {
+        match task.find(':') {
+            Some(idx) => self.modules.get_item(&task[..idx]),
+            None => None
+        }
+    }
+
+    ///
+    /// The names of all modules ordered by name.
+    ///
+    pub fn module_names(&self) -> Vec<&String> {
+        self.modules.names()
+    }
+
+    ///
+    /// The names of all tasks ordered by name.
+    ///
+    pub fn task_names(&self) -> Vec<&String> {
+        self.tasks.names()
+    }
+
     pub fn get_module_deps(&self, name: &str) -> Result<&HashSet<String>, Error> {
         self.modules.get_deps(name)
     }
@@ -64,6 +96,93 @@ impl Context {
         )
     }
 
+    ///
+    /// Checks that neither the modules nor the tasks depend on themselves. This should be called once all
+    /// modules and tasks have been added.
+    ///
+    pub fn finalize(&self) -> Result<(), Error> {
+        if let Some(cycle) = self.modules.find_cycle() {
+            let mut modules = cycle[1..].to_vec();
+            modules.sort();
+            Err(BuildError::DependencyCycle(
+                format!("Modules depend on each other: {} (modules: {})", cycle.join(" -> "), modules.join(", "))
+            ))?
+        }
+
+        if let Some(cycle) = self.tasks.find_cycle() {
+            let mut modules : Vec<String> = cycle
+                .iter()
+                .filter_map(|t| self.task_module(t))
+                .map(|m| m.name().clone())
+                .collect();
+            modules.sort();
+            modules.dedup();
+            Err(BuildError::DependencyCycle(
+                format!("Tasks depend on each other: {} (modules: {})", cycle.join(" -> "), modules.join(", "))
+            ))?
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Selects tasks by name. In the selector `*` matches any number of characters and `?` matches a single one, so
+    /// `*:clean` selects the clean task of every module. A selector starting with `^` additionally selects the same
+    /// task of every module depending, directly or not, on the module of a matched task: `^lib:build` selects
+    /// `lib:build` and the build task of everything using lib.
+    ///
+    /// Returns the selected tasks ordered by name. Selecting nothing is an error.
+    ///
+    pub fn select(&self, selector: &str) -> Result<Vec<String>, Error> {
+        let (dependents, pattern) = match selector.starts_with(DEPENDENTS_PREFIX) {
+            true => (true, &selector[DEPENDENTS_PREFIX.len_utf8()..]),
+            false => (false, selector)
+        };
+
+        let mut selected : BTreeSet<String> = self.task_names()
+            .into_iter()
+            .filter(|t| matches(pattern, t))
+            .cloned()
+            .collect();
+
+        if dependents {
+            for task in selected.clone() {
+                let (module, suffix) = match task.find(':') {
+                    Some(idx) => (&task[..idx], &task[idx..]),
+                    None => continue
+                };
+                for dependent in self.module_dependents(module)? {
+                    let name = format!("{}{}", dependent, suffix);
+                    if self.tasks.get_item(&name).is_some() {
+                        selected.insert(name);
+                    }
+                }
+            }
+        }
+
+        if selected.is_empty() {
+            Err(BuildError::NoSuchTask(format!("No task matches {}.", selector)))?
+        }
+        Ok(selected.into_iter().collect())
+    }
+
+    ///
+    /// The modules depending on the module, directly or not.
+    ///
+    fn module_dependents(&self, module: &str) -> Result<BTreeSet<String>, Error> {
+        let mut dependents = BTreeSet::new();
+        let mut stack = vec!(module.to_string());
+        while let Some(name) = stack.pop() {
+            for m in self.module_names() {
+                if !dependents.contains(m) && self.get_module_deps(m)?.contains(&name) {
+                    dependents.insert(m.clone());
+                    stack.push(m.clone());
+                }
+            }
+        }
+        Ok(dependents)
+    }
+
     pub fn task_mut(&mut self, name: &str) -> Result<TaskRef, Error> {
         self.tasks.get_item(name).ok_or(BuildError::NoSuchTask(name.to_string()))?;
         let name = String::from(name);
@@ -76,6 +195,36 @@ impl Context {
     }
 }
 
+///
+/// Matches a name against a pattern in which `*` matches any number of characters and `?` a single one.
+///
+fn matches(pattern: &str, name: &str) -> bool {
+    let pattern : Vec<char> = pattern.chars().collect();
+    let name : Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` in the pattern and of the name character it was matched against.
+    let mut star : Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p = p + 1;
+            n = n + 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p = p + 1;
+        } else if let Some((star_p, star_n)) = star {
+            // Let the last `*` match one more character and try again.
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
 impl <'a> TaskRef<'a> {
     pub fn depends_on(&mut self, name: &str) -> Result<(), Error> {
         self.context.tasks.set_dependency(&self.name, name)
@@ -92,7 +241,7 @@ impl <'a> ModuleRef<'a> {
 mod tests {
 
     use super::*;
-    use std::collections::HashMap;
+    use ::ConfigValue;
     use std::path::PathBuf;
 
     struct TestTask {}
@@ -133,14 +282,14 @@ mod tests {
             PathBuf::new(),
             PathBuf::new(),
             HashSet::new(),
-            HashMap::new()
+            ConfigValue::new()
         ));
         ctx.add_module("m1", Module::new(
             &"m0".to_string(),
             PathBuf::new(),
             PathBuf::new(),
             HashSet::new(),
-            HashMap::new()
+            ConfigValue::new()
         ));
         ctx.module("m1").unwrap().depends_on("m0").unwrap();
 
@@ -149,4 +298,89 @@ mod tests {
 
         assert_eq!(ctx.get_module_deps("m1").unwrap(), &expected);
     }
+
+    fn module(name: &str) -> Module {
+        Module::new(name, PathBuf::new(), PathBuf::new(), HashSet::new(), ConfigValue::new())
+    }
+
+    #[test]
+    fn module_cycle() {
+        let mut ctx = Context::new();
+        ctx.add_module("a", module("a"));
+        ctx.add_module("b", module("b"));
+        ctx.add_module("c", module("c"));
+        ctx.module("a").unwrap().depends_on("b").unwrap();
+        ctx.module("b").unwrap().depends_on("c").unwrap();
+        ctx.module("c").unwrap().depends_on("a").unwrap();
+
+        let err = ctx.finalize().unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<BuildError>().unwrap().get_message(),
+            "Modules depend on each other: a -> b -> c -> a (modules: a, b, c)"
+        );
+    }
+
+    #[test]
+    fn task_cycle() {
+        let mut ctx = Context::new();
+        ctx.add_module("a", module("a"));
+        ctx.add_module("b", module("b"));
+        ctx.add_task("a:build", Box::new(TestTask::new()));
+        ctx.add_task("b:build", Box::new(TestTask::new()));
+        ctx.add_task("build", Box::new(TestTask::new()));
+        ctx.task_mut("build").unwrap().depends_on("a:build").unwrap();
+        ctx.task_mut("a:build").unwrap().depends_on("b:build").unwrap();
+        ctx.task_mut("b:build").unwrap().depends_on("a:build").unwrap();
+
+        let err = ctx.finalize().unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<BuildError>().unwrap().get_message(),
+            "Tasks depend on each other: a:build -> b:build -> a:build (modules: a, b)"
+        );
+    }
+
+    #[test]
+    fn no_cycle() {
+        let mut ctx = Context::new();
+        ctx.add_task("task0", Box::new(TestTask::new()));
+        ctx.add_task("task1", Box::new(TestTask::new()));
+        ctx.add_task("task2", Box::new(TestTask::new()));
+        ctx.task_mut("task0").unwrap().depends_on("task1").unwrap();
+        ctx.task_mut("task0").unwrap().depends_on("task2").unwrap();
+        ctx.task_mut("task1").unwrap().depends_on("task2").unwrap();
+
+        assert!(ctx.finalize().is_ok());
+    }
+
+    fn select_context() -> Context {
+        let mut ctx = Context::new();
+        for m in &["lib", "net/a", "net/b", "app"] {
+            ctx.add_module(m, module(m));
+            ctx.add_task(&format!("{}:build", m), Box::new(TestTask::new()));
+            ctx.add_task(&format!("{}:clean", m), Box::new(TestTask::new()));
+        }
+        ctx.add_task("build", Box::new(TestTask::new()));
+        ctx.module("net/a").unwrap().depends_on("lib").unwrap();
+        ctx.module("app").unwrap().depends_on("net/a").unwrap();
+        ctx
+    }
+
+    #[test]
+    fn select_patterns() {
+        let ctx = select_context();
+
+        assert_eq!(ctx.select("build").unwrap(), vec!("build"));
+        assert_eq!(ctx.select("*:clean").unwrap(), vec!("app:clean", "lib:clean", "net/a:clean", "net/b:clean"));
+        assert_eq!(ctx.select("net/*:build").unwrap(), vec!("net/a:build", "net/b:build"));
+        assert_eq!(ctx.select("net/?:b*").unwrap(), vec!("net/a:build", "net/b:build"));
+        assert!(ctx.select("nothing:*").is_err());
+    }
+
+    #[test]
+    fn select_dependents() {
+        let ctx = select_context();
+
+        assert_eq!(ctx.select("^lib:build").unwrap(), vec!("app:build", "lib:build", "net/a:build"));
+        assert_eq!(ctx.select("^net/b:clean").unwrap(), vec!("net/b:clean"));
+    }
 }
\ No newline at end of file
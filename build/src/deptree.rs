@@ -49,9 +49,61 @@ impl<T: ?Sized> DepTree<T> {
         Ok(())
     }
 
+    ///
+    /// The names of all items ordered by name.
+    ///
+    pub fn names(&self) -> Vec<&String> {
+        let mut names : Vec<&String> = self.items.keys().collect();
+        names.sort();
+        names
+    }
+
     pub fn items_mut(&mut self) -> &HashMap<String, Box<T>> {
         &self.items
     }
+
+    ///
+    /// Returns the first dependency cycle found as a path that starts and ends with the same item.
+    ///
+    pub fn find_cycle(&self) -> Option<Vec<String>> {
+        let mut names : Vec<&String> = self.deps.keys().collect();
+        names.sort();
+
+        let mut visited = HashSet::new();
+        let mut path = vec!();
+        for name in names {
+            if let Some(cycle) = self.find_cycle_from(name, &mut visited, &mut path) {
+                return Some(cycle);
+            }
+        }
+        None
+    }
+
+    fn find_cycle_from(&self, name: &String, visited: &mut HashSet<String>, path: &mut Vec<String>) -> Option<Vec<String>> {
+        if let Some(start) = path.iter().position(|n| n == name) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(name.clone());
+            return Some(cycle);
+        }
+        if visited.contains(name) {
+            return None;
+        }
+
+        path.push(name.clone());
+        if let Some(deps) = self.deps.get(name) {
+            let mut deps : Vec<&String> = deps.iter().collect();
+            deps.sort();
+            for dep in deps {
+                if let Some(cycle) = self.find_cycle_from(dep, visited, path) {
+                    return Some(cycle);
+                }
+            }
+        }
+        path.pop();
+
+        visited.insert(name.clone());
+        None
+    }
 }
 
 impl<T: Sized> DepTree<T> {
@@ -5,6 +5,7 @@ use std::fmt;
 #[derive(Debug, Clone, Fail)]
 pub enum BuildError {
     ConfigError(String),
+    DependencyCycle(String),
     ExecutionError(String),
     IOError(String),
     NoSuchModule(String),
@@ -15,6 +16,7 @@ impl BuildError {
     pub fn get_message(&self) -> &String {
         match self {
             BuildError::ConfigError(msg) => &msg,
+            BuildError::DependencyCycle(msg) => &msg,
             BuildError::ExecutionError(msg) => &msg,
             BuildError::IOError(msg) => &msg,
             BuildError::NoSuchModule(msg) => &msg,
@@ -27,6 +29,7 @@ impl Display for BuildError {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         let (name, msg) = match &self {
             BuildError::ConfigError(msg) => ("ConfigError", msg),
+            BuildError::DependencyCycle(msg) => ("DependencyCycle", msg),
             BuildError::ExecutionError(msg) => ("ExecutionError", msg),
             BuildError::IOError(msg) => ("IOError", msg),
             BuildError::NoSuchModule(msg) => ("NoSuchModule", msg),
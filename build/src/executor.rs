@@ -1,22 +1,39 @@
 use ::Context;
 use ::Task;
 use failure::Error;
+use fingerprint::Fingerprint;
+use fingerprint::FingerprintStore;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::sync::mpsc;
+use std::thread;
+
+const DEFAULT_JOBS: usize = 1;
 
 pub struct Executor<'ctx> {
     context: &'ctx Context,
-    state: HashMap<String, ExecutionItem<'ctx>>
-}
-
-struct ExecutionItem<'ctx> {
-    task: &'ctx Task,
-    state: ExecutionState
+    jobs: usize,
+    keep_going: bool,
+    dry_run: bool,
+    state: HashMap<String, ExecutionState>,
+    errors: HashMap<String, Error>,
+    // Why each task did or did not execute, in the order the tasks finished.
+    decisions: Vec<(String, ExecutionReason)>,
+    store: FingerprintStore,
+    // Fingerprints of the tasks considered so far, including those of their upstream tasks.
+    fingerprints: HashMap<String, String>
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum ExecutionState {
     NotExecuted,
-    Done
+    Done(ExecutionBehavior),
+    Failed,
+    UpstreamFailed
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -25,91 +42,463 @@ enum ExecutionBehavior {
     Executed
 }
 
+///
+/// Why a task executed or did not.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecutionReason {
+    // The task itself is out of date.
+    OutOfDate,
+    // The named dependency executed.
+    DependencyExecuted(String),
+    UpToDate
+}
+
 #[derive(Debug, Fail)]
 enum ExecutionError {
-    #[fail(display = "dependency not found: {}", name)]
-    UnknownDependency{ name: String }
+    #[fail(display = "unable to schedule tasks: {:?}", names)]
+    Unschedulable{ names: Vec<String> },
+    #[fail(display = "tasks failed: {}", _0)]
+    Failed(String)
+}
+
+///
+/// The outcome of every task considered by an `Executor`, ordered by name.
+///
+pub struct ExecutionSummary {
+    executed: Vec<String>,
+    up_to_date: Vec<String>,
+    failed: Vec<String>,
+    skipped: Vec<String>
+}
+
+///
+/// The scheduling state for a single call to `Executor::execute`.
+///
+struct Schedule {
+    // Number of dependencies of each pending task that have not finished yet.
+    waiting_on: HashMap<String, usize>,
+    // Reverse edges: the pending tasks that depend on each task.
+    dependents: HashMap<String, Vec<String>>,
+    ready: VecDeque<String>
 }
 
 impl<'ctx> Executor<'ctx> {
     pub fn new(ctx: &Context) -> Executor {
         Executor {
             context: ctx,
-            state: HashMap::new()
+            jobs: DEFAULT_JOBS,
+            keep_going: false,
+            dry_run: false,
+            state: HashMap::new(),
+            errors: HashMap::new(),
+            decisions: vec!(),
+            store: FingerprintStore::new(),
+            fingerprints: HashMap::new()
         }
     }
 
-    fn get_execution(&mut self, task: &str) -> Result<&mut ExecutionItem<'ctx>, Error> {
-        let task = task.to_string();
-        if !self.state.contains_key(&task) {
-            self.state.insert(
-                task.to_string(), 
-                ExecutionItem {
-                    task: self.context.get_task(&task)?,
-                    state: ExecutionState::NotExecuted
-                }
-            );
+    ///
+    /// Sets the store used to decide whether fingerprinted tasks are up to date. It is saved after every execution.
+    ///
+    pub fn fingerprints(&mut self, store: FingerprintStore) -> &mut Executor<'ctx> {
+        self.store = store;
+        self
+    }
+
+    ///
+    /// Sets the maximum number of tasks that may execute at the same time.
+    ///
+    pub fn jobs(&mut self, jobs: usize) -> &mut Executor<'ctx> {
+        self.jobs = if jobs == 0 { 1 } else { jobs };
+        self
+    }
+
+    ///
+    /// When set, a failed task only prevents the tasks depending on it from executing. Everything else still executes.
+    ///
+    pub fn keep_going(&mut self, keep_going: bool) -> &mut Executor<'ctx> {
+        self.keep_going = keep_going;
+        self
+    }
+
+    ///
+    /// When set, tasks are considered as usual but never executed and no fingerprints are saved. The summary then
+    /// lists the tasks that would execute as executed.
+    ///
+    pub fn dry_run(&mut self, dry_run: bool) -> &mut Executor<'ctx> {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn summary(&self) -> ExecutionSummary {
+        let mut summary = ExecutionSummary {
+            executed: vec!(),
+            up_to_date: vec!(),
+            failed: vec!(),
+            skipped: vec!()
+        };
+
+        for (name, state) in &self.state {
+            match state {
+                ExecutionState::Done(ExecutionBehavior::Executed) => summary.executed.push(name.clone()),
+                ExecutionState::Done(ExecutionBehavior::Skipped) => summary.up_to_date.push(name.clone()),
+                ExecutionState::Failed => summary.failed.push(name.clone()),
+                ExecutionState::UpstreamFailed => summary.skipped.push(name.clone()),
+                ExecutionState::NotExecuted => ()
+            }
         }
-        match self.state.get_mut(&task) {
-            Some(t) => Ok(t),
-            None => Err(ExecutionError::UnknownDependency{name: task})?
+
+        summary.executed.sort();
+        summary.up_to_date.sort();
+        summary.failed.sort();
+        summary.skipped.sort();
+        summary
+    }
+
+    fn get_state(&self, task: &str) -> ExecutionState {
+        match self.state.get(task) {
+            Some(s) => *s,
+            None => ExecutionState::NotExecuted
         }
     }
 
-    fn execute_helper(&mut self, task: &str) -> Result<ExecutionBehavior, Error> {
-        let mut dep_behavior = ExecutionBehavior::Skipped;
-        let mut ret_behavior = ExecutionBehavior::Skipped;
+    fn get_task(&self, task: &str) -> Result<&'ctx Task, Error> {
+        self.context.get_task(task)
+    }
 
-        let deps = self.context.get_task_deps(task)?.clone();
-        let task = task.to_string();
+    ///
+    /// The error returned by a task that failed.
+    ///
+    pub fn error(&self, task: &str) -> Option<&Error> {
+        self.errors.get(task)
+    }
 
-        debug!("Considering: {} with dependencies: {:?}", task, deps);
+    ///
+    /// Collects every task reachable from `task` that has not been executed by a previous call.
+    ///
+    fn collect_pending(&self, task: &str, pending: &mut HashSet<String>) -> Result<(), Error> {
+        let mut stack = vec!(task.to_string());
 
-        for dep in deps {
-            let behavior = self.execute_helper(&dep)?;
+        while let Some(name) = stack.pop() {
+            if pending.contains(&name) || self.get_state(&name) != ExecutionState::NotExecuted {
+                continue;
+            }
+            self.get_task(&name)?;
 
-            // If any of the dependencies execute, then we consider the behavior to be "executed."
-            if dep_behavior == ExecutionBehavior::Skipped {
-                dep_behavior = behavior
+            for dep in self.context.get_task_deps(&name)? {
+                stack.push(dep.clone());
             }
+            pending.insert(name);
+        }
+
+        Ok(())
+    }
+
+    fn schedule(&self, tasks: &[String]) -> Result<Schedule, Error> {
+        let mut pending = HashSet::new();
+        for task in tasks {
+            self.collect_pending(task, &mut pending)?;
         }
 
-        let exec_item = self.get_execution(&task)?;
-        if exec_item.state() == ExecutionState::NotExecuted {
-            // If any dependency executed or if we need execution.
-            if dep_behavior == ExecutionBehavior::Executed || exec_item.task().needs_execution() {
-                info!("Executing: {}", task);
-                exec_item.task().execute()?;
-                ret_behavior = ExecutionBehavior::Executed;
+        let mut schedule = Schedule {
+            waiting_on: HashMap::new(),
+            dependents: HashMap::new(),
+            ready: VecDeque::new()
+        };
+
+        for name in &pending {
+            let mut count = 0;
+            for dep in self.context.get_task_deps(name)? {
+                if pending.contains(dep) {
+                    count = count + 1;
+                    schedule.dependents.entry(dep.clone()).or_insert(vec!()).push(name.clone());
+                }
+            }
+
+            if count == 0 {
+                schedule.ready.push_back(name.clone());
             } else {
-                info!("Skipping: {}", task);
+                schedule.waiting_on.insert(name.clone(), count);
             }
-            exec_item.set_done();
         }
 
-        debug!("Considering: {}. Behavior: {:?}", task, ret_behavior);
+        Ok(schedule)
+    }
 
-        Ok(ret_behavior)
+    ///
+    /// Why each task that finished did or did not execute, in the order they finished.
+    ///
+    pub fn decisions(&self) -> &Vec<(String, ExecutionReason)> {
+        &self.decisions
+    }
+
+    ///
+    /// Returns the first dependency of the task, by name, that executed.
+    ///
+    fn executed_dependency(&self, task: &str) -> Result<Option<String>, Error> {
+        let mut deps : Vec<&String> = self.context.get_task_deps(task)?.iter().collect();
+        deps.sort();
+        for dep in deps {
+            if self.get_state(dep) == ExecutionState::Done(ExecutionBehavior::Executed) {
+                return Ok(Some(dep.clone()));
+            }
+        }
+        Ok(None)
+    }
+
+    ///
+    /// Returns the fingerprints of the task's dependencies ordered by name.
+    ///
+    fn upstream_fingerprints(&self, task: &str) -> Result<Vec<(String, String)>, Error> {
+        let mut upstream : Vec<(String, String)> = self.context.get_task_deps(task)?
+            .iter()
+            .filter_map(|dep| self.fingerprints.get(dep).map(|f| (dep.clone(), f.clone())))
+            .collect();
+        upstream.sort();
+        Ok(upstream)
+    }
+
+    fn finish(&mut self, task: &str, reason: ExecutionReason, fingerprint: Option<String>, schedule: &mut Schedule) {
+        let behavior = if reason == ExecutionReason::UpToDate { ExecutionBehavior::Skipped } else { ExecutionBehavior::Executed };
+        debug!("Considering: {}. Behavior: {:?} ({})", task, behavior, reason);
+        self.state.insert(task.to_string(), ExecutionState::Done(behavior));
+        self.decisions.push((task.to_string(), reason));
+
+        if let Some(fingerprint) = fingerprint {
+            self.store.set(task, &fingerprint);
+            self.fingerprints.insert(task.to_string(), fingerprint);
+        }
+
+        if let Some(dependents) = schedule.dependents.remove(task) {
+            for dependent in dependents {
+                let ready = match schedule.waiting_on.get_mut(&dependent) {
+                    Some(count) => { *count = *count - 1; *count == 0 },
+                    None => false
+                };
+                if ready {
+                    schedule.waiting_on.remove(&dependent);
+                    schedule.ready.push_back(dependent);
+                }
+            }
+        }
+    }
+
+    ///
+    /// Marks every pending task that depends on the failed task, directly or not, as skipped.
+    ///
+    fn skip_dependents(&mut self, task: &str, schedule: &mut Schedule) {
+        let mut stack = vec!(task.to_string());
+
+        while let Some(name) = stack.pop() {
+            if let Some(dependents) = schedule.dependents.remove(&name) {
+                for dependent in dependents {
+                    if schedule.waiting_on.remove(&dependent).is_some() {
+                        warn!("Skipping {} because {} failed.", dependent, name);
+                        self.state.insert(dependent.clone(), ExecutionState::UpstreamFailed);
+                        stack.push(dependent);
+                    }
+                }
+            }
+        }
     }
 
     pub fn execute(&mut self, task: &str) -> Result<(), Error> {
-        self.execute_helper(task)?;
-        Ok(())
+        self.execute_all(&[task.to_string()])
+    }
+
+    ///
+    /// Executes several tasks at once. Tasks they have in common are only considered once.
+    ///
+    pub fn execute_all(&mut self, tasks: &[String]) -> Result<(), Error> {
+        let result = self.execute_schedule(tasks);
+        if self.dry_run {
+            return result;
+        }
+
+        // Keep the fingerprints of everything that succeeded, even if another task failed.
+        if let Err(e) = self.store.save() {
+            error!("{}", e);
+            result?;
+            return Err(e);
+        }
+
+        result
+    }
+
+    fn execute_schedule(&mut self, tasks: &[String]) -> Result<(), Error> {
+        let mut schedule = self.schedule(tasks)?;
+        let (sender, receiver) = mpsc::channel::<(String, Result<(ExecutionReason, Option<String>), Error>)>();
+        let jobs = self.jobs;
+        let dry_run = self.dry_run;
+
+        thread::scope(|scope| -> Result<(), Error> {
+            let mut running = 0;
+            let mut failed : Vec<String> = vec!();
+
+            loop {
+                // Start as many ready tasks as we are allowed to, unless something already failed.
+                while (failed.is_empty() || self.keep_going) && running < jobs {
+                    let name = match schedule.ready.pop_front() {
+                        Some(name) => name,
+                        None => break
+                    };
+
+                    let task = self.get_task(&name)?;
+                    let executed_dependency = self.executed_dependency(&name)?;
+                    let previous = self.store.get(&name).cloned();
+                    let upstream = self.upstream_fingerprints(&name)?;
+                    let sender = sender.clone();
+
+                    debug!("Considering: {} with dependencies: {:?}", name, self.context.get_task_deps(&name)?);
+
+                    running = running + 1;
+                    scope.spawn(move || {
+                        let result = execute_task(&name, task, executed_dependency, previous, upstream, dry_run);
+                        // The receiver outlives every worker so this cannot fail.
+                        let _ = sender.send((name, result));
+                    });
+                }
+
+                if running == 0 {
+                    break;
+                }
+
+                let (name, result) = match receiver.recv() {
+                    Ok(r) => r,
+                    Err(_) => break
+                };
+                running = running - 1;
+
+                match result {
+                    Ok((reason, fingerprint)) => self.finish(&name, reason, fingerprint, &mut schedule),
+                    Err(e) => {
+                        error!("Task {} failed: {}", name, e);
+                        self.state.insert(name.clone(), ExecutionState::Failed);
+                        self.errors.insert(name.clone(), e);
+
+                        if self.keep_going {
+                            self.skip_dependents(&name, &mut schedule);
+                        } else if !failed.is_empty() {
+                            debug!("Additional failure while stopping: {}", name);
+                        }
+                        failed.push(name);
+                    }
+                }
+            }
+
+            if !failed.is_empty() {
+                failed.sort();
+                Err(ExecutionError::Failed(failed.join(", ")))?
+            }
+
+            if !schedule.waiting_on.is_empty() {
+                let mut names : Vec<String> = schedule.waiting_on.keys().cloned().collect();
+                names.sort();
+                Err(ExecutionError::Unschedulable{ names: names })?
+            }
+
+            Ok(())
+        })
     }
 }
 
-impl<'a> ExecutionItem<'a> {
+impl ExecutionSummary {
+    ///
+    /// Tasks that executed successfully.
+    ///
+    pub fn executed(&self) -> &Vec<String> {
+        &self.executed
+    }
+
+    ///
+    /// Tasks that did not need to execute.
+    ///
+    pub fn up_to_date(&self) -> &Vec<String> {
+        &self.up_to_date
+    }
 
-    fn task(&self) -> &Task {
-        self.task
+    pub fn failed(&self) -> &Vec<String> {
+        &self.failed
     }
 
-    fn state(&self) -> ExecutionState {
-        self.state
+    ///
+    /// Tasks that were not executed because a task they depend on failed.
+    ///
+    pub fn skipped(&self) -> &Vec<String> {
+        &self.skipped
     }
+}
 
-    fn set_done(&mut self) {
-        self.state = ExecutionState::Done
+impl Display for ExecutionReason {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ExecutionReason::OutOfDate => write!(f, "self out of date"),
+            ExecutionReason::DependencyExecuted(dep) => write!(f, "dependency {} executes", dep),
+            ExecutionReason::UpToDate => write!(f, "up to date")
+        }
+    }
+}
+
+///
+/// Runs a single task. Executes if any dependency executed or if the task itself is out of date. A fingerprinted
+/// task is out of date when its fingerprint, combined with those of its dependencies, differs from `previous`.
+///
+/// Returns why the task executed and the combined fingerprint so that it can be recorded and passed on to dependent
+/// tasks. The fingerprint of a task that executed is taken again afterwards since executing may change what it reads,
+/// for example whether its outputs exist.
+///
+fn execute_task(
+    name: &str,
+    task: &Task,
+    executed_dependency: Option<String>,
+    previous: Option<String>,
+    upstream: Vec<(String, String)>,
+    dry_run: bool
+) -> Result<(ExecutionReason, Option<String>), Error> {
+    let fingerprint = combined_fingerprint(task, &upstream)?;
+
+    let out_of_date = match &fingerprint {
+        Some(f) => {
+            trace!("Fingerprint of {}: {} (previously {:?})", name, f, previous);
+            previous.as_ref() != Some(f)
+        },
+        None => task.needs_execution()
+    };
+
+    let reason = match (out_of_date, executed_dependency) {
+        (true, _) => ExecutionReason::OutOfDate,
+        (false, Some(dep)) => ExecutionReason::DependencyExecuted(dep),
+        (false, None) => ExecutionReason::UpToDate
+    };
+
+    if dry_run {
+        debug!("{}: {}", name, reason);
+    } else if reason != ExecutionReason::UpToDate {
+        info!("Executing: {}", name);
+        task.execute()?;
+        return Ok((reason, combined_fingerprint(task, &upstream)?));
+    } else {
+        info!("Skipping: {}", name);
+    }
+
+    Ok((reason, fingerprint))
+}
+
+///
+/// The fingerprint of a task combined with those of its dependencies, or None if the task has no fingerprint.
+///
+fn combined_fingerprint(task: &Task, upstream: &[(String, String)]) -> Result<Option<String>, Error> {
+    match task.fingerprint()? {
+        Some(own) => {
+            let mut fingerprint = Fingerprint::new();
+            fingerprint.add(own);
+            for (dep, dep_fingerprint) in upstream {
+                fingerprint.add(dep).add(dep_fingerprint);
+            }
+            Ok(Some(fingerprint.finish()))
+        },
+        None => Ok(None)
     }
 }
 
@@ -118,18 +507,19 @@ impl<'a> ExecutionItem<'a> {
 mod tests {
 
     use super::*;
-    use std::collections::HashMap;
-    use std::cell::RefCell;
-    use std::path::PathBuf;
-    use std::ops::DerefMut;
-    use std::rc::Rc;
+    use error::BuildError;
+    use std::sync::Arc;
+    use std::sync::Barrier;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
 
     struct TestTask {
-        flag: Rc<RefCell<bool>>
+        flag: Arc<AtomicBool>
     }
 
     impl TestTask {
-        fn new(flag: Rc<RefCell<bool>>) -> TestTask {
+        fn new(flag: Arc<AtomicBool>) -> TestTask {
             TestTask {
                 flag: flag
             }
@@ -141,13 +531,94 @@ mod tests {
             true
         }
         fn execute(&self) -> Result<(), Error> {
-            *self.flag.borrow_mut().deref_mut() = true;
+            self.flag.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    struct UpToDateTask {
+        flag: Arc<AtomicBool>
+    }
+
+    impl Task for UpToDateTask {
+        fn needs_execution(&self) -> bool {
+            false
+        }
+        fn execute(&self) -> Result<(), Error> {
+            self.flag.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    struct BarrierTask {
+        barrier: Arc<Barrier>
+    }
+
+    impl Task for BarrierTask {
+        fn needs_execution(&self) -> bool {
+            true
+        }
+        fn execute(&self) -> Result<(), Error> {
+            // Only returns once every task sharing the barrier is running at the same time.
+            self.barrier.wait();
             Ok(())
         }
     }
 
-    fn flag() -> Rc<RefCell<bool>> {
-        Rc::new(RefCell::new(false))
+    struct FailingTask {
+        count: Arc<AtomicUsize>
+    }
+
+    impl Task for FailingTask {
+        fn needs_execution(&self) -> bool {
+            true
+        }
+        fn execute(&self) -> Result<(), Error> {
+            self.count.fetch_add(1, Ordering::SeqCst);
+            Err(BuildError::ExecutionError("failure".to_string()))?
+        }
+    }
+
+    struct FingerprintTask {
+        fingerprint: String,
+        count: Arc<AtomicUsize>
+    }
+
+    impl Task for FingerprintTask {
+        fn needs_execution(&self) -> bool {
+            true
+        }
+        fn execute(&self) -> Result<(), Error> {
+            self.count.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+        fn fingerprint(&self) -> Result<Option<String>, Error> {
+            Ok(Some(self.fingerprint.clone()))
+        }
+    }
+
+    struct OutputTask {
+        built: Arc<AtomicBool>,
+        count: Arc<AtomicUsize>
+    }
+
+    impl Task for OutputTask {
+        fn needs_execution(&self) -> bool {
+            true
+        }
+        fn execute(&self) -> Result<(), Error> {
+            self.built.store(true, Ordering::SeqCst);
+            self.count.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+        fn fingerprint(&self) -> Result<Option<String>, Error> {
+            // Like a build task whose fingerprint includes whether its outputs exist.
+            Ok(Some(if self.built.load(Ordering::SeqCst) { "built" } else { "clean" }.to_string()))
+        }
+    }
+
+    fn flag() -> Arc<AtomicBool> {
+        Arc::new(AtomicBool::new(false))
     }
 
     #[test]
@@ -172,10 +643,223 @@ mod tests {
         let mut executor = Executor::new(&ctx);
         executor.execute("task0").unwrap();
 
-        assert_eq!(*(*flag0).borrow(), true);
-        assert_eq!(*(*flag1).borrow(), true);
-        assert_eq!(*(*flag2).borrow(), true);
-        assert_eq!(*(*flag3).borrow(), true);
-        assert_eq!(*(*flag4).borrow(), false);
+        assert_eq!(flag0.load(Ordering::SeqCst), true);
+        assert_eq!(flag1.load(Ordering::SeqCst), true);
+        assert_eq!(flag2.load(Ordering::SeqCst), true);
+        assert_eq!(flag3.load(Ordering::SeqCst), true);
+        assert_eq!(flag4.load(Ordering::SeqCst), false);
+    }
+
+    #[test]
+    fn executed_dependency_forces_execution() {
+        let flag0 = flag();
+        let flag1 = flag();
+        let flag2 = flag();
+
+        let mut ctx = Context::new();
+        ctx.add_task("task0", Box::new(UpToDateTask { flag: flag0.clone() }));
+        ctx.add_task("task1", Box::new(TestTask::new(flag1.clone())));
+        ctx.add_task("task2", Box::new(UpToDateTask { flag: flag2.clone() }));
+
+        ctx.task_mut("task0").unwrap().depends_on("task1").unwrap();
+        ctx.task_mut("task1").unwrap().depends_on("task2").unwrap();
+
+        Executor::new(&ctx).jobs(4).execute("task0").unwrap();
+
+        assert_eq!(flag0.load(Ordering::SeqCst), true);
+        assert_eq!(flag1.load(Ordering::SeqCst), true);
+        assert_eq!(flag2.load(Ordering::SeqCst), false);
+    }
+
+    #[test]
+    fn parallel_execution() {
+        let barrier = Arc::new(Barrier::new(3));
+
+        let mut ctx = Context::new();
+        ctx.add_task("all", Box::new(TestTask::new(flag())));
+        for name in &["task0", "task1", "task2"] {
+            ctx.add_task(name, Box::new(BarrierTask { barrier: barrier.clone() }));
+            ctx.task_mut("all").unwrap().depends_on(name).unwrap();
+        }
+
+        // This would deadlock if the three independent tasks were not run at the same time.
+        Executor::new(&ctx).jobs(3).execute("all").unwrap();
+    }
+
+    #[test]
+    fn failure_stops_execution() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let flag0 = flag();
+        let flag1 = flag();
+
+        let mut ctx = Context::new();
+        ctx.add_task("task0", Box::new(TestTask::new(flag0.clone())));
+        ctx.add_task("task1", Box::new(FailingTask { count: count.clone() }));
+        ctx.add_task("task2", Box::new(TestTask::new(flag1.clone())));
+
+        ctx.task_mut("task0").unwrap().depends_on("task1").unwrap();
+        ctx.task_mut("task2").unwrap().depends_on("task0").unwrap();
+
+        let mut executor = Executor::new(&ctx);
+        assert!(executor.jobs(2).execute("task2").is_err());
+        assert_eq!(executor.summary().failed(), &vec!("task1".to_string()));
+        assert_eq!(executor.error("task1").unwrap().to_string(), "ExecutionError: failure");
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        assert_eq!(flag0.load(Ordering::SeqCst), false);
+        assert_eq!(flag1.load(Ordering::SeqCst), false);
+    }
+
+    #[test]
+    fn multiple_targets() {
+        let shared_count = Arc::new(AtomicUsize::new(0));
+        let flag0 = flag();
+        let flag1 = flag();
+
+        let mut ctx = Context::new();
+        ctx.add_task("shared", Box::new(FingerprintTask { fingerprint: "shared".to_string(), count: shared_count.clone() }));
+        ctx.add_task("task0", Box::new(TestTask::new(flag0.clone())));
+        ctx.add_task("task1", Box::new(TestTask::new(flag1.clone())));
+        ctx.task_mut("task0").unwrap().depends_on("shared").unwrap();
+        ctx.task_mut("task1").unwrap().depends_on("shared").unwrap();
+
+        Executor::new(&ctx).jobs(2).execute_all(&["task0".to_string(), "task1".to_string()]).unwrap();
+
+        assert_eq!(shared_count.load(Ordering::SeqCst), 1);
+        assert_eq!(flag0.load(Ordering::SeqCst), true);
+        assert_eq!(flag1.load(Ordering::SeqCst), true);
+    }
+
+    fn fingerprint_context(lib: &str, bin: &str, lib_count: &Arc<AtomicUsize>, bin_count: &Arc<AtomicUsize>) -> Context {
+        let mut ctx = Context::new();
+        ctx.add_task("lib", Box::new(FingerprintTask { fingerprint: lib.to_string(), count: lib_count.clone() }));
+        ctx.add_task("bin", Box::new(FingerprintTask { fingerprint: bin.to_string(), count: bin_count.clone() }));
+        ctx.task_mut("bin").unwrap().depends_on("lib").unwrap();
+        ctx
+    }
+
+    #[test]
+    fn fingerprints() {
+        let lib_count = Arc::new(AtomicUsize::new(0));
+        let bin_count = Arc::new(AtomicUsize::new(0));
+
+        let run = |lib: &str, bin: &str, store: FingerprintStore| -> FingerprintStore {
+            let ctx = fingerprint_context(lib, bin, &lib_count, &bin_count);
+            let mut executor = Executor::new(&ctx);
+            executor.fingerprints(store);
+            executor.execute("bin").unwrap();
+            executor.store
+        };
+
+        // Nothing has been recorded so everything executes.
+        let store = run("lib0", "bin0", FingerprintStore::new());
+        assert_eq!(lib_count.load(Ordering::SeqCst), 1);
+        assert_eq!(bin_count.load(Ordering::SeqCst), 1);
+
+        // Unchanged fingerprints are up to date.
+        let store = run("lib0", "bin0", store);
+        assert_eq!(lib_count.load(Ordering::SeqCst), 1);
+        assert_eq!(bin_count.load(Ordering::SeqCst), 1);
+
+        // A changed upstream fingerprint also changes the fingerprint of its dependents.
+        run("lib1", "bin0", store);
+        assert_eq!(lib_count.load(Ordering::SeqCst), 2);
+        assert_eq!(bin_count.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn fingerprints_after_execution() {
+        let built = flag();
+        let lib_count = Arc::new(AtomicUsize::new(0));
+        let bin_count = Arc::new(AtomicUsize::new(0));
+
+        let mut ctx = Context::new();
+        ctx.add_task("lib", Box::new(OutputTask { built: built.clone(), count: lib_count.clone() }));
+        ctx.add_task("bin", Box::new(FingerprintTask { fingerprint: "bin".to_string(), count: bin_count.clone() }));
+        ctx.task_mut("bin").unwrap().depends_on("lib").unwrap();
+
+        let mut executor = Executor::new(&ctx);
+        executor.execute("bin").unwrap();
+        let store = executor.store;
+        assert_eq!(lib_count.load(Ordering::SeqCst), 1);
+        assert_eq!(bin_count.load(Ordering::SeqCst), 1);
+
+        // The outputs written by the first run are part of the recorded fingerprint so nothing executes again.
+        let mut executor = Executor::new(&ctx);
+        executor.fingerprints(store);
+        executor.execute("bin").unwrap();
+        assert!(executor.summary().executed().is_empty());
+        assert_eq!(lib_count.load(Ordering::SeqCst), 1);
+        assert_eq!(bin_count.load(Ordering::SeqCst), 1);
+
+        // Removing the outputs makes the task and its dependents out of date.
+        built.store(false, Ordering::SeqCst);
+        let store = executor.store;
+        let mut executor = Executor::new(&ctx);
+        executor.fingerprints(store);
+        executor.execute("bin").unwrap();
+        assert_eq!(lib_count.load(Ordering::SeqCst), 2);
+        assert_eq!(bin_count.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn keep_going() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let flag0 = flag();
+        let flag1 = flag();
+        let flag2 = flag();
+
+        let mut ctx = Context::new();
+        ctx.add_task("all", Box::new(TestTask::new(flag0.clone())));
+        ctx.add_task("broken", Box::new(FailingTask { count: count.clone() }));
+        ctx.add_task("dependent", Box::new(TestTask::new(flag1.clone())));
+        ctx.add_task("independent", Box::new(TestTask::new(flag2.clone())));
+
+        ctx.task_mut("dependent").unwrap().depends_on("broken").unwrap();
+        ctx.task_mut("all").unwrap().depends_on("dependent").unwrap();
+        ctx.task_mut("all").unwrap().depends_on("independent").unwrap();
+
+        let mut executor = Executor::new(&ctx);
+        assert!(executor.keep_going(true).execute("all").is_err());
+
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        assert_eq!(flag0.load(Ordering::SeqCst), false);
+        assert_eq!(flag1.load(Ordering::SeqCst), false);
+        assert_eq!(flag2.load(Ordering::SeqCst), true);
+
+        let summary = executor.summary();
+        assert_eq!(summary.executed(), &vec!("independent".to_string()));
+        assert_eq!(summary.failed(), &vec!("broken".to_string()));
+        assert_eq!(summary.skipped(), &vec!("all".to_string(), "dependent".to_string()));
+    }
+
+    #[test]
+    fn dry_run() {
+        let flag0 = flag();
+        let flag1 = flag();
+        let flag2 = flag();
+
+        let mut ctx = Context::new();
+        ctx.add_task("task0", Box::new(UpToDateTask { flag: flag0.clone() }));
+        ctx.add_task("task1", Box::new(TestTask::new(flag1.clone())));
+        ctx.add_task("task2", Box::new(UpToDateTask { flag: flag2.clone() }));
+        ctx.task_mut("task0").unwrap().depends_on("task1").unwrap();
+        ctx.task_mut("task1").unwrap().depends_on("task2").unwrap();
+
+        let mut executor = Executor::new(&ctx);
+        executor.dry_run(true).execute("task0").unwrap();
+
+        assert_eq!(flag0.load(Ordering::SeqCst), false);
+        assert_eq!(flag1.load(Ordering::SeqCst), false);
+        assert_eq!(flag2.load(Ordering::SeqCst), false);
+
+        let summary = executor.summary();
+        assert_eq!(summary.executed(), &vec!("task0".to_string(), "task1".to_string()));
+        assert_eq!(summary.up_to_date(), &vec!("task2".to_string()));
+
+        assert_eq!(executor.decisions(), &vec!(
+            ("task2".to_string(), ExecutionReason::UpToDate),
+            ("task1".to_string(), ExecutionReason::OutOfDate),
+            ("task0".to_string(), ExecutionReason::DependencyExecuted("task1".to_string()))
+        ));
     }
 }
\ No newline at end of file
@@ -0,0 +1,172 @@
+use error::BuildError;
+use failure::Error;
+use sha2::Digest;
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+///
+/// Accumulates the inputs of a task into a single content hash.
+///
+pub struct Fingerprint {
+    hasher: Sha256
+}
+
+///
+/// The fingerprints of every task as of its last successful execution.
+///
+/// The store is a text file with one "<task>\t<fingerprint>" line per task.
+///
+pub struct FingerprintStore {
+    path: Option<PathBuf>,
+    entries: BTreeMap<String, String>
+}
+
+impl Fingerprint {
+    pub fn new() -> Fingerprint {
+        Fingerprint {
+            hasher: Sha256::new()
+        }
+    }
+
+    pub fn add<T: AsRef<[u8]>>(&mut self, data: T) -> &mut Fingerprint {
+        let data = data.as_ref();
+        // Prefix the length so that ("ab", "c") and ("a", "bc") hash differently.
+        self.hasher.update((data.len() as u64).to_le_bytes());
+        self.hasher.update(data);
+        self
+    }
+
+    pub fn add_file(&mut self, path: &Path) -> Result<&mut Fingerprint, Error> {
+        match fs::read(path) {
+            Ok(content) => Ok(self.add(content)),
+            Err(e) => Err(BuildError::IOError(format!("Unable to read {}: {}", path.display(), e)))?
+        }
+    }
+
+    pub fn finish(&self) -> String {
+        self.hasher
+            .clone()
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
+
+impl FingerprintStore {
+    ///
+    /// Creates a store that is never written to disk.
+    ///
+    pub fn new() -> FingerprintStore {
+        FingerprintStore {
+            path: None,
+            entries: BTreeMap::new()
+        }
+    }
+
+    ///
+    /// Loads the store at the given path. A missing file is treated as an empty store.
+    ///
+    pub fn load(path: &Path) -> Result<FingerprintStore, Error> {
+        let mut store = FingerprintStore {
+            path: Some(path.to_path_buf()),
+            entries: BTreeMap::new()
+        };
+
+        if !path.exists() {
+            return Ok(store);
+        }
+
+        let content = match fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) => Err(BuildError::IOError(format!("Unable to read fingerprints from {}: {}", path.display(), e)))?
+        };
+
+        for (i, line) in content.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            match line.rfind('\t') {
+                Some(idx) => { store.entries.insert(line[..idx].to_string(), line[idx + 1..].to_string()); },
+                None => Err(BuildError::IOError(format!("Malformed fingerprint in {} on line {}.", path.display(), i + 1)))?
+            }
+        }
+
+        Ok(store)
+    }
+
+    pub fn get(&self, task: &str) -> Option<&String> {
+        self.entries.get(task)
+    }
+
+    pub fn set(&mut self, task: &str, fingerprint: &str) {
+        self.entries.insert(task.to_string(), fingerprint.to_string());
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let path = match &self.path {
+            Some(p) => p,
+            None => return Ok(())
+        };
+
+        if let Some(dir) = path.parent() {
+            if !dir.as_os_str().is_empty() && !dir.exists() {
+                if let Err(e) = fs::create_dir_all(dir) {
+                    Err(BuildError::IOError(format!("Unable to create directory {}: {}", dir.display(), e)))?
+                }
+            }
+        }
+
+        let content : String = self.entries
+            .iter()
+            .map(|(k, v)| format!("{}\t{}\n", k, v))
+            .collect();
+
+        if let Err(e) = fs::write(path, content) {
+            Err(BuildError::IOError(format!("Unable to write fingerprints to {}: {}", path.display(), e)))?
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn fingerprint_is_deterministic() {
+        let a = Fingerprint::new().add("a").add("bc").finish();
+        let b = Fingerprint::new().add("a").add("bc").finish();
+        let c = Fingerprint::new().add("ab").add("c").finish();
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a.len(), 64);
+    }
+
+    #[test]
+    fn store_round_trip() {
+        let path = env::temp_dir()
+            .join(format!("mib-fingerprint-test-{}", process::id()))
+            .join("fingerprints");
+
+        let mut store = FingerprintStore::load(&path).unwrap();
+        assert_eq!(store.get("m:build"), None);
+
+        store.set("m:build", "abc");
+        store.set("other:build", "def");
+        store.save().unwrap();
+
+        let store = FingerprintStore::load(&path).unwrap();
+        assert_eq!(store.get("m:build"), Some(&"abc".to_string()));
+        assert_eq!(store.get("other:build"), Some(&"def".to_string()));
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
\ No newline at end of file
@@ -1,15 +1,26 @@
+extern crate sha2;
+
 #[macro_use] extern crate failure;
 #[macro_use] extern crate log;
 
+mod config_value;
 mod context;
 mod deptree;
 mod error;
 mod executor;
+mod fingerprint;
+mod location;
 mod module;
 mod task;
 
+pub use config_value::ConfigValue;
 pub use context::Context;
 pub use error::BuildError;
+pub use executor::ExecutionReason;
+pub use executor::ExecutionSummary;
 pub use executor::Executor;
+pub use fingerprint::Fingerprint;
+pub use fingerprint::FingerprintStore;
+pub use location::Location;
 pub use module::Module;
 pub use task::Task;
\ No newline at end of file
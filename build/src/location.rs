@@ -0,0 +1,92 @@
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+///
+/// A position in a build file. Used to point at the source of configuration errors.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    file: String,
+    // Both start at 1.
+    line: usize,
+    column: usize,
+    source_line: String
+}
+
+impl Location {
+    ///
+    /// Creates the location of the given line and column in the source of the named file.
+    ///
+    pub fn new(file: &str, source: &str, line: usize, column: usize) -> Location {
+        Location {
+            file: file.to_string(),
+            line: line,
+            column: column,
+            source_line: source.lines().nth(line.saturating_sub(1)).unwrap_or("").to_string()
+        }
+    }
+
+    pub fn file(&self) -> &String {
+        &self.file
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    ///
+    /// Formats the message prefixed with this location and followed by the source line with a caret under the
+    /// column, for example:
+    ///
+    /// ```text
+    /// build.yml:9:9: Unknown module hellolb.
+    ///   |
+    /// 9 |       - hellolb
+    ///   |         ^
+    /// ```
+    ///
+    pub fn describe(&self, message: &str) -> String {
+        let number = self.line.to_string();
+        let margin = " ".repeat(number.len());
+        // Keep tabs so that the caret lines up with the source line.
+        let indent : String = self.source_line
+            .chars()
+            .take(self.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        format!(
+            "{}: {}\n{} |\n{} | {}\n{} | {}^",
+            self, message, margin, number, self.source_line, margin, indent
+        )
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn describe() {
+        let source = "module:\n  - name: a\n    deps:\n      - b\n";
+        let location = Location::new("build.yml", source, 4, 9);
+
+        assert_eq!(location.to_string(), "build.yml:4:9");
+        assert_eq!(
+            location.describe("Unknown module b."),
+            "build.yml:4:9: Unknown module b.\n  |\n4 |       - b\n  |         ^"
+        );
+    }
+}
\ No newline at end of file
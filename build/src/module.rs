@@ -1,3 +1,5 @@
+use ::ConfigValue;
+use ::Location;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::path::PathBuf;
@@ -6,8 +8,15 @@ pub struct Module {
     name: String,
     module_dir: PathBuf,
     build_dir: PathBuf,
+    // The build directories of every profile, including build_dir.
+    build_dirs: Vec<PathBuf>,
     types: HashSet<String>,
-    config: HashMap<String, String>
+    config_tree: ConfigValue,
+    // Flat view of the configuration for plugins that only deal with strings.
+    config: HashMap<String, String>,
+    location: Option<Location>,
+    // Where each key of the flat view was defined.
+    config_locations: HashMap<String, Location>
 }
 
 impl Module {
@@ -16,17 +25,42 @@ impl Module {
         module_dir: PathBuf,
         build_dir: PathBuf,
         types: HashSet<String>,
-        config: HashMap<String, String>
+        config: ConfigValue
     ) -> Module {
         Module {
             name: name.to_string(),
             module_dir: module_dir,
+            build_dirs: vec!(build_dir.clone()),
             build_dir: build_dir,
             types: types,
-            config: config
+            config: config.flatten(),
+            config_tree: config,
+            location: None,
+            config_locations: HashMap::new()
         }
     }
 
+    ///
+    /// Sets where the module was defined.
+    ///
+    pub fn set_location(&mut self, location: Location) {
+        self.location = Some(location);
+    }
+
+    ///
+    /// Sets where a configuration key, with nested keys joined by dots, was defined.
+    ///
+    pub fn set_config_location(&mut self, key: &str, location: Location) {
+        self.config_locations.insert(key.to_string(), location);
+    }
+
+    ///
+    /// Sets the build directories of every profile, so that plugins can tell them apart from the module's sources.
+    ///
+    pub fn set_build_dirs(&mut self, build_dirs: Vec<PathBuf>) {
+        self.build_dirs = build_dirs;
+    }
+
     pub fn name(&self) -> &String {
         &self.name
     }
@@ -39,11 +73,54 @@ impl Module {
         &self.build_dir
     }
 
+    ///
+    /// The build directories of every profile, including that of the selected one.
+    ///
+    pub fn build_dirs(&self) -> &Vec<PathBuf> {
+        &self.build_dirs
+    }
+
     pub fn types(&self) -> &HashSet<String> {
         &self.types
     }
 
+    ///
+    /// The configuration with nested keys joined by dots and every value converted to a string.
+    ///
     pub fn config(&self) -> &HashMap<String, String> {
         &self.config
     }
+
+    ///
+    /// The configuration as written in the build file.
+    ///
+    pub fn config_tree(&self) -> &ConfigValue {
+        &self.config_tree
+    }
+
+    ///
+    /// Looks up a configuration value by its dotted key, see `ConfigValue::get`.
+    ///
+    pub fn config_value(&self, key: &str) -> Option<&ConfigValue> {
+        self.config_tree.get(key)
+    }
+
+    pub fn location(&self) -> Option<&Location> {
+        self.location.as_ref()
+    }
+
+    ///
+    /// Where a configuration key was defined. Falls back to the closest enclosing key for keys of nested maps.
+    ///
+    pub fn config_location(&self, key: &str) -> Option<&Location> {
+        if let Some(location) = self.config_locations.get(key) {
+            return Some(location);
+        }
+        for (idx, _) in key.rmatch_indices('.') {
+            if let Some(location) = self.config_locations.get(&key[..idx]) {
+                return Some(location);
+            }
+        }
+        None
+    }
 }
\ No newline at end of file
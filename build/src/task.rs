@@ -1,6 +1,14 @@
 use failure::Error;
 
-pub trait Task {
+pub trait Task: Send + Sync {
     fn needs_execution(&self) -> bool;
     fn execute(&self) -> Result<(), Error>;
+
+    ///
+    /// A hash of everything this task reads. Tasks that return a fingerprint only execute when it differs from the
+    /// fingerprint recorded after their last successful execution and `needs_execution` is not consulted.
+    ///
+    fn fingerprint(&self) -> Result<Option<String>, Error> {
+        Ok(None)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,197 @@
+use build::ConfigValue;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::env;
+
+// Placeholders of environment variables start with this prefix.
+const ENV_PREFIX: &'static str = "env:";
+
+///
+/// A placeholder that could not be resolved.
+///
+#[derive(Debug, PartialEq)]
+pub struct InterpolationError {
+    // The dotted config key whose value holds the placeholder.
+    pub key: String,
+    pub message: String
+}
+
+///
+/// Resolves `${...}` placeholders in every string of a config value. A placeholder refers to
+/// * an environment variable when it starts with "env:", for example `${env:CONAN_USER}`,
+/// * one of the given variables, for example `${module.name}`,
+/// * or another key of the config, for example `${project.version}`, whose value must not be a list or a map.
+///
+/// Any other placeholder, such as the CMake variable `${CMAKE_SOURCE_DIR}`, is left as it is. `$${` is replaced with a
+/// literal `${`.
+///
+pub fn interpolate(config: &ConfigValue, variables: &HashMap<String, String>) -> Result<ConfigValue, InterpolationError> {
+    let mut interpolator = Interpolator {
+        config: config,
+        variables: variables,
+        stack: vec!()
+    };
+    match config {
+        ConfigValue::Map(map) => interpolator.map(None, map),
+        _ => Ok(config.clone())
+    }
+}
+
+struct Interpolator<'a> {
+    config: &'a ConfigValue,
+    variables: &'a HashMap<String, String>,
+    // The keys being resolved to detect keys that refer to each other.
+    stack: Vec<String>
+}
+
+impl <'a> Interpolator<'a> {
+    fn map(&mut self, prefix: Option<&str>, map: &BTreeMap<String, ConfigValue>) -> Result<ConfigValue, InterpolationError> {
+        let mut resolved = BTreeMap::new();
+        for (k, v) in map {
+            let key = match prefix {
+                Some(prefix) => format!("{}.{}", prefix, k),
+                None => k.clone()
+            };
+            resolved.insert(k.clone(), self.value(&key, v)?);
+        }
+        Ok(ConfigValue::Map(resolved))
+    }
+
+    fn value(&mut self, key: &str, value: &ConfigValue) -> Result<ConfigValue, InterpolationError> {
+        Ok(match value {
+            ConfigValue::String(s) => ConfigValue::String(self.string(key, s)?),
+            ConfigValue::List(items) => {
+                let mut resolved = vec!();
+                for item in items {
+                    resolved.push(self.value(key, item)?);
+                }
+                ConfigValue::List(resolved)
+            },
+            ConfigValue::Map(map) => self.map(Some(key), map)?,
+            v => v.clone()
+        })
+    }
+
+    fn string(&mut self, key: &str, s: &str) -> Result<String, InterpolationError> {
+        let mut resolved = String::new();
+        let mut rest = s;
+        while let Some(start) = rest.find("${") {
+            // An escaped placeholder.
+            if rest[..start].ends_with('$') {
+                resolved.push_str(&rest[..start - 1]);
+                resolved.push_str("${");
+                rest = &rest[start + 2..];
+                continue;
+            }
+
+            resolved.push_str(&rest[..start]);
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => return Err(error(key, format!("The placeholder in \"{}\" is not closed with }}.", s)))
+            };
+            resolved.push_str(&self.lookup(key, &rest[start + 2..end])?);
+            rest = &rest[end + 1..];
+        }
+        resolved.push_str(rest);
+        Ok(resolved)
+    }
+
+    fn lookup(&mut self, key: &str, name: &str) -> Result<String, InterpolationError> {
+        if name.starts_with(ENV_PREFIX) {
+            let variable = &name[ENV_PREFIX.len()..];
+            return match env::var(variable) {
+                Ok(v) => Ok(v),
+                Err(_) => Err(error(key, format!("Environment variable {} is not set.", variable)))
+            };
+        }
+        if let Some(v) = self.variables.get(name) {
+            return Ok(v.clone());
+        }
+
+        match self.config.get(name) {
+            Some(ConfigValue::String(s)) => {
+                if self.stack.iter().any(|k| k == name) {
+                    let mut cycle = self.stack.clone();
+                    cycle.push(name.to_string());
+                    return Err(error(key, format!("Config keys refer to each other: {}", cycle.join(" -> "))));
+                }
+                self.stack.push(name.to_string());
+                let resolved = self.string(key, s);
+                self.stack.pop();
+                resolved
+            },
+            Some(ConfigValue::List(_)) | Some(ConfigValue::Map(_)) => {
+                Err(error(key, format!("${{{}}} refers to a list or map which can not be inserted into a string.", name)))
+            },
+            Some(v) => Ok(v.to_string()),
+            None => {
+                trace!("Leaving ${{{}}} in {} as it is.", name, key);
+                Ok(format!("${{{}}}", name))
+            }
+        }
+    }
+}
+
+fn error(key: &str, message: String) -> InterpolationError {
+    InterpolationError {
+        key: key.to_string(),
+        message: message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn map(entries: &[(&str, ConfigValue)]) -> ConfigValue {
+        ConfigValue::Map(entries.iter().map(|(k, v)| (k.to_string(), v.clone())).collect())
+    }
+
+    fn string(s: &str) -> ConfigValue {
+        ConfigValue::String(s.to_string())
+    }
+
+    #[test]
+    fn placeholders() {
+        env::set_var("MIB_INTERPOLATION_TEST", "me");
+        let mut variables = HashMap::new();
+        variables.insert("module.name".to_string(), "hello".to_string());
+        let config = map(&[
+            ("project", map(&[("version", ConfigValue::Float(1.5))])),
+            ("conan.version", string("${project.version}")),
+            ("conan.user", string("${env:MIB_INTERPOLATION_TEST}")),
+            ("conan.name", ConfigValue::List(vec!(string("lib${module.name}"), string("$${CMAKE_BINARY_DIR}"))))
+        ]);
+
+        let resolved = interpolate(&config, &variables).unwrap();
+        assert_eq!(resolved.get("conan.version"), Some(&string("1.5")));
+        assert_eq!(resolved.get("conan.user"), Some(&string("me")));
+        assert_eq!(
+            resolved.get("conan.name"),
+            Some(&ConfigValue::List(vec!(string("libhello"), string("${CMAKE_BINARY_DIR}"))))
+        );
+    }
+
+    #[test]
+    fn unresolved() {
+        let variables = HashMap::new();
+
+        // CMake variables in definitions pass through, even next to a placeholder that is resolved.
+        let config = map(&[
+            ("project", string("hello")),
+            ("cmake.definitions", map(&[("ASSETS", string("${CMAKE_SOURCE_DIR}/${project}/assets"))]))
+        ]);
+        let resolved = interpolate(&config, &variables).unwrap();
+        assert_eq!(resolved.get("cmake.definitions.ASSETS"), Some(&string("${CMAKE_SOURCE_DIR}/hello/assets")));
+
+        let config = map(&[("a", map(&[("b", string("x${env:MIB_INTERPOLATION_UNSET}"))]))]);
+        let e = interpolate(&config, &variables).unwrap_err();
+        assert_eq!(e.key, "a.b");
+
+        let config = map(&[("a", string("${b}")), ("b", string("${a}"))]);
+        let e = interpolate(&config, &variables).unwrap_err();
+        assert_eq!(e.key, "a");
+        assert_eq!(e.message, "Config keys refer to each other: b -> a -> b");
+    }
+}
\ No newline at end of file
@@ -1,13 +1,21 @@
 extern crate build;
+extern crate glob;
+extern crate serde;
+extern crate serde_json;
 extern crate serde_yaml;
 extern crate simple_logger;
+extern crate toml;
 extern crate yaml_rust;
 
 #[macro_use] extern crate failure;
 #[macro_use] extern crate log;
 #[macro_use] extern crate serde_derive;
 
+mod interpolation;
 mod parser;
+mod platform;
+mod source_map;
 
+pub use parser::Options;
 pub use parser::parse;
 pub use parser::parse_file;
\ No newline at end of file
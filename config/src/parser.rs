@@ -1,139 +1,895 @@
 use build;
+use build::BuildError;
+use build::ConfigValue;
+use build::Location;
+use glob;
+use interpolation;
+use platform::CONDITIONS;
+use platform::Platform;
+use serde::de::DeserializeOwned;
+use serde_json;
 use serde_yaml;
 use serde_yaml::Value;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::env;
+use std::fs;
 use std::fs::File;
 use std::io::prelude::*;
 use std::path::Path;
 use std::path::PathBuf;
 use failure::Error;
+use source_map::SourceMap;
+use toml;
 
 pub const DEFAULT_MODULE_TYPE: &'static str = "conan";
 pub const DEFAULT_BUILD_FOLDER: &'static str = "build";
+// Used in locations when parsing a string rather than a file.
+const STRING_SOURCE_NAME: &'static str = "<string>";
+// The files describing a module in a discovered directory, in the order they are looked for.
+const MODULE_FILE_NAMES: &'static [&'static str] = &["module.yml", "module.yaml", "module.toml", "module.json"];
+// Defines with this prefix, like "-D platform.compiler=clang", override a condition of the platform.
+const PLATFORM_DEFINE_PREFIX: &'static str = "platform.";
+
+///
+/// The formats that build files can be written in. All have the same schema.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Yaml,
+    Toml,
+    Json
+}
+
+///
+/// Options that change how a build file is read.
+///
+pub struct Options {
+    profile: Option<String>,
+    // The target triple that conditional config is evaluated against, the host when not set.
+    triple: Option<String>,
+    // Config set on the command line, applied on top of everything else. Keys starting with "platform." override a
+    // condition of the platform instead.
+    defines: Vec<(String, String)>
+}
 
 #[derive(Deserialize, Debug)]
 struct Config {
+    // Other build files whose modules, defaults and profiles are added to this one.
+    include: Option<Vec<String>>,
+    // Patterns of directories holding a module file each.
+    discover: Option<Vec<String>>,
     default: Option<Default>,
+    #[serde(default)]
     module: Vec<Module>,
+    profile: Option<BTreeMap<String, Profile>>
 }
 
 #[derive(Deserialize, Debug, PartialEq)]
 struct Default {
     #[serde(rename="type")]
     module_type: Option<String>,
-    config: Option<BTreeMap<String, Value>>
+    config: Option<BTreeMap<String, Value>>,
+    when: Option<Vec<When>>
 }
 
 #[derive(Deserialize, Debug)]
 struct Module {
     name: String,
     path: Option<String>,
+    #[serde(rename="type")]
     module_type: Option<String>,
     deps: Option<Vec<String>>,
+    config: Option<BTreeMap<String, Value>>,
+    when: Option<Vec<When>>
+}
+
+///
+/// Config applied on top of the default or module config when every condition matches the target platform, for
+/// example `{os: [linux, macos], arch: x86_64, config: {...}}`.
+///
+#[derive(Deserialize, Debug, PartialEq)]
+struct When {
+    config: Option<BTreeMap<String, Value>>,
+    #[serde(flatten)]
+    conditions: BTreeMap<String, Value>
+}
+
+///
+/// A named config overlay applied on top of the default and module config of every module.
+///
+#[derive(Deserialize, Debug)]
+struct Profile {
     config: Option<BTreeMap<String, Value>>
 }
 
-impl Default {
-    fn new() -> Default {
-        Default {
-            module_type: None,
-            config: None
+///
+/// A parsed build file.
+///
+struct Document {
+    config: Config,
+    source_map: SourceMap,
+    // The directory that paths of modules in this file are relative to.
+    base: PathBuf,
+    // Whether this is the module file of a discovered module rather than a build file.
+    manifest: bool
+}
+
+///
+/// Configuration combined from several documents together with where each key was defined.
+///
+struct Layer {
+    config: ConfigValue,
+    locations: HashMap<String, Location>
+}
+
+impl Options {
+    pub fn new() -> Options {
+        Options {
+            profile: None,
+            triple: None,
+            defines: vec!()
+        }
+    }
+
+    ///
+    /// Selects a profile defined in the build file. Modules of a profile are built in "build-<profile>".
+    ///
+    pub fn profile(&mut self, profile: Option<String>) -> &mut Options {
+        self.profile = profile;
+        self
+    }
+
+    ///
+    /// Evaluates "when" blocks against a target triple such as "x86_64-pc-windows-msvc" instead of the host.
+    ///
+    pub fn triple(&mut self, triple: Option<String>) -> &mut Options {
+        self.triple = triple;
+        self
+    }
+
+    ///
+    /// Sets a config key of every module, overriding the build file. A key such as "platform.compiler" overrides the
+    /// condition of the platform that "when" blocks are evaluated against instead.
+    ///
+    pub fn define(&mut self, key: &str, value: &str) -> &mut Options {
+        self.defines.push((key.to_string(), value.to_string()));
+        self
+    }
+}
+
+impl Format {
+    ///
+    /// The format of a file, chosen by its extension.
+    ///
+    fn of(path: &Path) -> Result<Format, Error> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("yml") | Some("yaml") => Ok(Format::Yaml),
+            Some("toml") => Ok(Format::Toml),
+            Some("json") => Ok(Format::Json),
+            _ => Err(BuildError::ConfigError(format!(
+                "Unable to determine the format of {} from its extension. Build files end in .yml, .yaml, .toml or .json.",
+                path.display()
+            )))?
+        }
+    }
+}
+
+impl Document {
+    ///
+    /// The source map path of a key of the i-th module. A module file holds a single module at its top level.
+    ///
+    fn module_path(&self, i: usize, key: &str) -> String {
+        match (self.manifest, key.is_empty()) {
+            // Map entries are located at their key so point at the name.
+            (true, true) => "name".to_string(),
+            (true, false) => key.to_string(),
+            (false, true) => format!("module.{}", i),
+            (false, false) => format!("module.{}.{}", i, key)
+        }
+    }
+}
+
+impl Layer {
+    fn new() -> Layer {
+        Layer {
+            config: ConfigValue::new(),
+            locations: HashMap::new()
+        }
+    }
+
+    ///
+    /// Adds the config found at the path of the document on top of this layer.
+    ///
+    fn add(&mut self, config: &Option<BTreeMap<String, Value>>, document: &Document, path: &str) -> Result<(), Error> {
+        if let Some(config) = config {
+            add_config_to_map(&mut self.config, config)?;
+            for (key, location) in document.source_map.locations_below(path) {
+                self.locations.insert(key, location);
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// Adds the config of the "when" blocks found at the path of the document whose conditions match the platform.
+    ///
+    fn add_matching(&mut self, blocks: &Option<Vec<When>>, document: &Document, path: &str, platform: &Platform) -> Result<(), Error> {
+        for (i, block) in blocks.iter().flat_map(|b| b.iter()).enumerate() {
+            let block_path = format!("{}.{}", path, i);
+            if matches(block, platform, document, &block_path)? {
+                self.add(&block.config, document, &format!("{}.config", block_path))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+///
+/// Whether every condition of a "when" block matches the platform. A condition matches a name or any of a list of
+/// names.
+///
+fn matches(block: &When, platform: &Platform, document: &Document, path: &str) -> Result<bool, Error> {
+    for (condition, expected) in &block.conditions {
+        let condition_path = format!("{}.{}", path, condition);
+        let actual = match platform.get(condition) {
+            Some(a) => a,
+            None => Err(located_error(
+                &document.source_map,
+                &condition_path,
+                format!("Unknown condition {}. Conditions are {}.", condition, CONDITIONS.join(", "))
+            ))?
+        };
+        let matched = match expected {
+            Value::String(s) => s == actual,
+            Value::Sequence(names) if names.iter().all(|n| n.is_string()) => names.iter().any(|n| n.as_str() == Some(actual)),
+            _ => Err(located_error(
+                &document.source_map,
+                &condition_path,
+                format!("Condition {} must be a name or a list of names.", condition)
+            ))?
+        };
+        if !matched {
+            return Ok(false);
         }
     }
+    Ok(true)
 }
 
-pub fn parse_file(context: &mut build::Context, f: &Path) -> Result<(), Error> {
+///
+/// Parses a build file in the format given by its extension.
+///
+pub fn parse_file(context: &mut build::Context, f: &Path, options: &Options) -> Result<(), Error> {
+    let format = Format::of(f)?;
     let mut file = File::open(f)?;
     let mut filecontent : String = String::new();
     file.read_to_string(&mut filecontent)?;
-    return parse(context, &filecontent);
+
+    let dir = f.parent().unwrap_or(Path::new(""));
+    let mut documents = vec!();
+    let mut stack = vec!(canonicalize(f)?);
+    let mut loaded = stack.iter().cloned().collect();
+    load(&mut documents, &mut stack, &mut loaded, &filecontent, format, &f.display().to_string(), dir, PathBuf::new())?;
+
+    let root = canonicalize(if dir.as_os_str().is_empty() { Path::new(".") } else { dir })?;
+    add_documents(context, &documents, &root, options)
 }
 
-pub fn parse(context: &mut build::Context, s: &String) -> Result<(), Error> {
-    let mut build: Config = serde_yaml::from_str(s)?;
+///
+/// Parses a build file in YAML.
+///
+pub fn parse(context: &mut build::Context, s: &String, options: &Options) -> Result<(), Error> {
+    let mut documents = vec!();
+    load(&mut documents, &mut vec!(), &mut HashSet::new(), s, Format::Yaml, STRING_SOURCE_NAME, Path::new(""), PathBuf::new())?;
 
-    debug!("{:#?}", build);
+    add_documents(context, &documents, &env::current_dir()?, options)
+}
 
-    if build.default == None {
-        build.default = Some(Default::new())
-    }
+///
+/// Parses a build file and, before it, every file it includes. Included files are read relative to `dir`, the
+/// directory of the including file, and their modules are relative to the directory of the included file.
+///
+/// Directories matching a discover pattern that contain a module file add the module described by it, located in
+/// that directory.
+///
+/// `stack` holds the files being included to detect cycles. Files that were already loaded are skipped.
+///
+fn load(
+    documents: &mut Vec<Document>,
+    stack: &mut Vec<PathBuf>,
+    loaded: &mut HashSet<PathBuf>,
+    s: &str,
+    format: Format,
+    name: &str,
+    dir: &Path,
+    base: PathBuf
+) -> Result<(), Error> {
+    let source_map = index(name, s, format);
+    let config: Config = deserialize(s, format, &source_map)?;
+
+    debug!("{:#?}", config);
+
+    for (i, include) in config.include.iter().flat_map(|i| i.iter()).enumerate() {
+        let error = |message: String| located_error(&source_map, &format!("include.{}", i), message);
 
-    // Set a default plugin.
-    if build.default.as_ref().unwrap().module_type == None {
-        build.default.as_mut().unwrap().module_type = Some(DEFAULT_MODULE_TYPE.to_string());
+        for file in expand(dir, include, true).map_err(|e| error(e))? {
+            let canonical = canonicalize(&file).map_err(|e| error(e.to_string()))?;
+            if stack.contains(&canonical) {
+                let mut cycle : Vec<String> = stack.iter().map(|p| p.display().to_string()).collect();
+                cycle.push(canonical.display().to_string());
+                Err(error(format!("Build files include each other: {}", cycle.join(" -> "))))?
+            }
+            if !loaded.insert(canonical.clone()) {
+                debug!("Skipping {} which was already included.", file.display());
+                continue;
+            }
+
+            let format = Format::of(&file).map_err(|e| error(e.to_string()))?;
+            let content = fs::read_to_string(&file).map_err(|e| error(format!("Unable to read {}: {}", file.display(), e)))?;
+            let file_dir = file.parent().unwrap_or(Path::new(""));
+            let relative_dir = file_dir.strip_prefix(dir).unwrap_or(file_dir);
+
+            stack.push(canonical);
+            load(documents, stack, loaded, &content, format, &file.display().to_string(), file_dir, base.join(relative_dir))?;
+            stack.pop();
+        }
     }
 
-    // Add modules
-    for m in &build.module {
-        let mut config : HashMap<String, String> = HashMap::new();
-        let plugin = m.module_type.as_ref().unwrap_or(build.default.as_ref().unwrap().module_type.as_ref().unwrap());
+    for (i, pattern) in config.discover.iter().flat_map(|d| d.iter()).enumerate() {
+        let error = |message: String| located_error(&source_map, &format!("discover.{}", i), message);
 
-        let default_config = build.default.as_ref().unwrap();
+        for module_dir in expand(dir, pattern, false).map_err(|e| error(e))? {
+            let file = match MODULE_FILE_NAMES.iter().map(|n| module_dir.join(n)).find(|f| f.is_file()) {
+                Some(f) => f,
+                None => {
+                    debug!("Skipping {} which has no module file.", module_dir.display());
+                    continue;
+                }
+            };
+            if !loaded.insert(canonicalize(&file).map_err(|e| error(e.to_string()))?) {
+                continue;
+            }
+
+            let content = fs::read_to_string(&file).map_err(|e| error(format!("Unable to read {}: {}", file.display(), e)))?;
+            let file_name = file.display().to_string();
+            let format = Format::of(&file)?;
+            let module_map = index(&file_name, &content, format);
+            let module: Module = deserialize(&content, format, &module_map)?;
+            let relative_dir = module_dir.strip_prefix(dir).unwrap_or(&module_dir);
 
-        if let Some(default_config) = &default_config.config {
-            add_config_to_map(&mut config, &default_config);
+            documents.push(Document {
+                config: Config {
+                    include: None,
+                    discover: None,
+                    default: None,
+                    module: vec!(module),
+                    profile: None
+                },
+                source_map: module_map,
+                base: base.join(relative_dir),
+                manifest: true
+            });
         }
+    }
+
+    documents.push(Document {
+        config: config,
+        source_map: source_map,
+        base: base,
+        manifest: false
+    });
+    Ok(())
+}
 
-        if let Some(module_conf) = &m.config {
-            add_config_to_map(&mut config, &module_conf)
+///
+/// Indexes the positions of a document. TOML documents are not indexed so errors in them only point at syntax errors.
+///
+fn index(name: &str, s: &str, format: Format) -> SourceMap {
+    match format {
+        // JSON is valid YAML.
+        Format::Yaml | Format::Json => SourceMap::new(name, s),
+        Format::Toml => SourceMap::unindexed(name, s)
+    }
+}
+
+///
+/// Deserializes a document, pointing errors at their location in the source.
+///
+fn deserialize<T: DeserializeOwned>(s: &str, format: Format, source_map: &SourceMap) -> Result<T, Error> {
+    // The message and the 1-based line and column of the error.
+    let (message, position) = match format {
+        Format::Yaml => match serde_yaml::from_str(s) {
+            Ok(t) => return Ok(t),
+            Err(e) => (e.to_string(), e.location().map(|l| (l.line(), l.column())))
+        },
+        Format::Json => match serde_json::from_str(s) {
+            Ok(t) => return Ok(t),
+            Err(e) => (e.to_string(), if e.line() > 0 { Some((e.line(), e.column())) } else { None })
+        },
+        Format::Toml => match toml::from_str(s) {
+            Ok(t) => return Ok(t),
+            Err(e) => (e.to_string(), e.line_col().map(|(l, c)| (l + 1, c + 1)))
         }
+    };
+
+    match position {
+        Some((line, column)) => {
+            // The location is shown separately.
+            let message = message.replace(&format!(" at line {} column {}", line, column), "");
+            Err(BuildError::ConfigError(source_map.location_at(line, column).describe(&message)))?
+        },
+        None => Err(BuildError::ConfigError(format!("{}: {}", source_map.file(), message)))?
+    }
+}
 
-        let module_path = match &m.path {
-            Some(p) => PathBuf::from(p),
-            None => PathBuf::from(&m.name)
+fn located_error(source_map: &SourceMap, path: &str, message: String) -> Error {
+    match source_map.location(path) {
+        Some(l) => BuildError::ConfigError(l.describe(&message)).into(),
+        None => BuildError::ConfigError(message).into()
+    }
+}
+
+///
+/// The files, or directories, matching a pattern ordered by name. A pattern without wildcards must name an existing
+/// file or directory.
+///
+fn expand(dir: &Path, pattern: &str, files: bool) -> Result<Vec<PathBuf>, String> {
+    let kind = if files { "file" } else { "directory" };
+    let wanted = |p: &Path| if files { p.is_file() } else { p.is_dir() };
+
+    let path = dir.join(pattern);
+    if !pattern.contains(|c| c == '*' || c == '?' || c == '[') {
+        return match wanted(&path) {
+            true => Ok(vec!(path)),
+            false => Err(format!("The {} {} does not exist.", kind, path.display()))
         };
-        let build_dir = module_path.join(DEFAULT_BUILD_FOLDER);
-        let mut types = HashSet::new();
-        types.insert(plugin.clone());
+    }
+
+    let paths = match glob::glob(&path.to_string_lossy()) {
+        Ok(p) => p,
+        Err(e) => return Err(format!("Invalid pattern {}: {}", pattern, e))
+    };
+    let mut matches = vec!();
+    for path in paths {
+        match path {
+            Ok(p) => if wanted(&p) { matches.push(p) },
+            Err(e) => return Err(format!("Unable to read {}: {}", e.path().display(), e.error()))
+        }
+    }
+    matches.sort();
+    Ok(matches)
+}
+
+fn canonicalize(path: &Path) -> Result<PathBuf, Error> {
+    match fs::canonicalize(path) {
+        Ok(p) => Ok(p),
+        Err(e) => Err(BuildError::IOError(format!("Unable to resolve {}: {}", path.display(), e)))?
+    }
+}
+
+///
+/// Adds the modules of every document to the context. Defaults and profiles of later documents override those of
+/// earlier ones, so a build file overrides the files it includes.
+///
+/// `root` is the directory of the root build file and resolves `${root}` placeholders in config values.
+///
+fn add_documents(context: &mut build::Context, documents: &Vec<Document>, root: &Path, options: &Options) -> Result<(), Error> {
+    let mut platform = match &options.triple {
+        Some(t) => Platform::from_triple(t).map_err(|e| BuildError::ConfigError(e))?,
+        None => Platform::host()
+    };
+    for (key, value) in &options.defines {
+        if key.starts_with(PLATFORM_DEFINE_PREFIX) {
+            platform.set(&key[PLATFORM_DEFINE_PREFIX.len()..], value).map_err(|e| BuildError::ConfigError(format!("-D {}: {}", key, e)))?;
+        }
+    }
+    debug!("Evaluating conditional config for {:?}", platform);
 
-        context.add_module(
-            &m.name,
-            build::Module::new(
+    let mut default_type = DEFAULT_MODULE_TYPE.to_string();
+    let mut defaults = Layer::new();
+    let mut profiles : BTreeMap<String, Layer> = BTreeMap::new();
+
+    for document in documents {
+        if let Some(default) = &document.config.default {
+            if let Some(module_type) = &default.module_type {
+                default_type = module_type.clone();
+            }
+            defaults.add(&default.config, document, "default.config")?;
+            defaults.add_matching(&default.when, document, "default.when", &platform)?;
+        }
+        for (name, profile) in document.config.profile.iter().flat_map(|p| p.iter()) {
+            let layer = profiles.entry(name.clone()).or_insert(Layer::new());
+            layer.add(&profile.config, document, &format!("profile.{}.config", name))?;
+        }
+    }
+
+    // Find the selected profile.
+    let profile = match &options.profile {
+        Some(p) => match profiles.get(p) {
+            Some(profile) => Some((p, profile)),
+            None => {
+                let names : Vec<&str> = profiles.keys().map(|n| n.as_str()).collect();
+                Err(BuildError::ConfigError(format!("Unknown profile {}. Available profiles: {}.", p, names.join(", "))))?
+            }
+        },
+        None => None
+    };
+
+    // Add modules
+    let mut definitions : HashMap<String, Option<Location>> = HashMap::new();
+    for document in documents {
+        for (i, m) in document.config.module.iter().enumerate() {
+            let location = document.source_map.location(&document.module_path(i, ""));
+            if let Some(previous) = definitions.get(&m.name) {
+                let mut message = format!("Module {} is defined more than once.", m.name);
+                if let Some(previous) = previous {
+                    message = format!("{} It was first defined at {}.", message, previous);
+                }
+                match &location {
+                    Some(l) => Err(BuildError::ConfigError(l.describe(&message)))?,
+                    None => Err(BuildError::ConfigError(message))?
+                }
+            }
+            definitions.insert(m.name.clone(), location.clone());
+
+            let mut config = defaults.config.clone();
+            let mut locations = defaults.locations.clone();
+
+            let mut module_config = Layer::new();
+            module_config.add(&m.config, document, &document.module_path(i, "config"))?;
+            module_config.add_matching(&m.when, document, &document.module_path(i, "when"), &platform)?;
+            config.merge(module_config.config);
+            locations.extend(module_config.locations);
+
+            if let Some((_, profile)) = profile {
+                config.merge(profile.config.clone());
+                locations.extend(profile.locations.clone());
+            }
+            for (key, value) in options.defines.iter().filter(|(k, _)| !k.starts_with(PLATFORM_DEFINE_PREFIX)) {
+                let mut define = BTreeMap::new();
+                define.insert(key.clone(), ConfigValue::String(value.clone()));
+                config.merge(ConfigValue::Map(define));
+                locations.remove(key);
+            }
+
+            let module_path = match &m.path {
+                Some(p) => document.base.join(p),
+                // A discovered module lives in the directory of its module file.
+                None if document.manifest => document.base.clone(),
+                None => document.base.join(&m.name)
+            };
+
+            let mut variables = HashMap::new();
+            variables.insert("module.name".to_string(), m.name.clone());
+            variables.insert("module.dir".to_string(), root.join(&module_path).display().to_string());
+            variables.insert("root".to_string(), root.display().to_string());
+            let config = match interpolation::interpolate(&config, &variables) {
+                Ok(config) => config,
+                Err(e) => {
+                    // Keys set on the command line are not in any build file.
+                    if options.defines.iter().any(|(k, _)| *k == e.key) {
+                        Err(BuildError::ConfigError(format!("Module {}: {} set with -D: {}", m.name, e.key, e.message)))?
+                    }
+                    let message = format!("Module {}: {}: {}", m.name, e.key, e.message);
+                    match closest_location(&locations, &e.key).or(location.as_ref()) {
+                        Some(l) => Err(BuildError::ConfigError(l.describe(&message)))?,
+                        None => Err(BuildError::ConfigError(message))?
+                    }
+                }
+            };
+            let build_dir = get_build_dir(&module_path, profile.map(|(p, _)| p.as_str()));
+            let build_dirs = Some(module_path.join(DEFAULT_BUILD_FOLDER)).into_iter()
+                .chain(profiles.keys().map(|p| get_build_dir(&module_path, Some(p))))
+                .collect();
+            let mut types = HashSet::new();
+            types.insert(m.module_type.as_ref().unwrap_or(&default_type).clone());
+
+            let mut module = build::Module::new(
                 &m.name,
                 module_path,
                 build_dir,
                 types,
                 config
-            )
-        );
+            );
+
+            module.set_build_dirs(build_dirs);
+
+            // Remember where things were defined so that plugins can point at them.
+            if let Some(location) = location {
+                module.set_location(location);
+            }
+            for (key, location) in locations {
+                module.set_config_location(&key, location);
+            }
+
+            context.add_module(&m.name, module);
+        }
     }
 
     // Add module dependencies
-    for m in &build.module {
-        if let Some(deps) = &m.deps {
-            for dep in deps {
+    for document in documents {
+        for (i, m) in document.config.module.iter().enumerate() {
+            for (j, dep) in m.deps.iter().flat_map(|d| d.iter()).enumerate() {
+                if context.get_module(dep).is_err() {
+                    let message = format!("Module {} depends on unknown module {}.", m.name, dep);
+                    match document.source_map.location(&document.module_path(i, &format!("deps.{}", j))) {
+                        Some(l) => Err(BuildError::ConfigError(l.describe(&message)))?,
+                        None => Err(BuildError::ConfigError(message))?
+                    }
+                }
                 context.module(&m.name)?.depends_on(dep)?;
             }
         }
     }
 
+    // Reject modules that depend on each other.
+    context.finalize()?;
+
     return Ok(());
 }
 
-fn add_config_to_map(config: &mut HashMap<String,String>, src: &BTreeMap<String,Value>) {
+///
+/// The build folder of a module. Every profile has its own build folder so that switching profiles does not remove the
+/// other builds.
+///
+fn get_build_dir(module_path: &Path, profile: Option<&str>) -> PathBuf {
+    match profile {
+        Some(p) => module_path.join(format!("{}-{}", DEFAULT_BUILD_FOLDER, p)),
+        None => module_path.join(DEFAULT_BUILD_FOLDER)
+    }
+}
+
+///
+/// The location of a dotted key or else of the closest enclosing key.
+///
+fn closest_location<'a>(locations: &'a HashMap<String, Location>, key: &str) -> Option<&'a Location> {
+    if let Some(location) = locations.get(key) {
+        return Some(location);
+    }
+    key.rmatch_indices('.').filter_map(|(idx, _)| locations.get(&key[..idx])).next()
+}
+
+fn add_config_to_map(config: &mut ConfigValue, src: &BTreeMap<String,Value>) -> Result<(), Error> {
+    let mut map = BTreeMap::new();
     for (key,v) in src {
-        match v {
-            Value::String(v) => {config.insert(key.clone(), v.clone());},
-            Value::Bool(v) => {config.insert(key.clone(), v.to_string());},
-            Value::Number(v) => {config.insert(key.clone(), v.to_string());},
-            Value::Sequence(s) => {
-                // TODO: Handle these "unexpected" values more gracefully.
-                let value : Vec<String> = s.iter()
-                    .map(|x| {
-                        match x {
-                            Value::String(x) => x.clone(),
-                            Value::Bool(b) => b.to_string(),
-                            Value::Mapping(_) => "unexpected_map".to_string(),
-                            Value::Null => "null".to_string(),
-                            Value::Number(n) => n.to_string(),
-                            Value::Sequence(_) => "unexpected_sequence".to_string()
-                        }
-                    }).collect();
-                config.insert(key.clone(), value.join(","));
-            },
-            v => panic!(format!("Config value for key {} must be a string but was {:?}.", key.clone(), v))
+        map.insert(key.clone(), to_config_value(key, v)?);
+    }
+    config.merge(ConfigValue::Map(map));
+    Ok(())
+}
+
+///
+/// Converts a YAML value to a config value. Null is treated as an empty string.
+///
+fn to_config_value(key: &str, v: &Value) -> Result<ConfigValue, Error> {
+    Ok(match v {
+        Value::String(v) => ConfigValue::String(v.clone()),
+        Value::Bool(v) => ConfigValue::Bool(*v),
+        Value::Number(v) => match (v.as_i64(), v.as_f64()) {
+            (Some(n), _) => ConfigValue::Integer(n),
+            (None, Some(n)) if v.is_f64() => ConfigValue::Float(n),
+            // Integers too large for an i64 are kept as they were written.
+            _ => ConfigValue::String(v.to_string())
+        },
+        Value::Null => ConfigValue::String(String::new()),
+        Value::Sequence(s) => {
+            let mut items = vec!();
+            for (i, x) in s.iter().enumerate() {
+                items.push(to_config_value(&format!("{}[{}]", key, i), x)?);
+            }
+            ConfigValue::List(items)
+        },
+        Value::Mapping(m) => {
+            let mut map = BTreeMap::new();
+            for (k, x) in m {
+                let k = match k {
+                    Value::String(k) => k.clone(),
+                    Value::Bool(k) => k.to_string(),
+                    Value::Number(k) => k.to_string(),
+                    k => Err(BuildError::ConfigError(format!("Config key in {} must be a string but was {:?}.", key, k)))?
+                };
+                let value = to_config_value(&format!("{}.{}", key, k), x)?;
+                map.insert(k, value);
+            }
+            ConfigValue::Map(map)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::process;
+
+    ///
+    /// Writes the files of a project to a new temporary directory.
+    ///
+    fn project(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("mib-parser-{}-{}", name, process::id()));
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        for (path, content) in files {
+            let file = dir.join(path);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(file, content).unwrap();
+        }
+        dir
+    }
+
+    fn parse_project(dir: &Path) -> Result<build::Context, Error> {
+        let mut context = build::Context::new();
+        parse_file(&mut context, &dir.join("build.yml"), &Options::new())?;
+        Ok(context)
+    }
+
+    fn parse_error(dir: &Path) -> String {
+        match parse_project(dir) {
+            Err(e) => e.to_string(),
+            Ok(_) => panic!("{} was parsed without errors.", dir.display())
         }
     }
+
+    #[test]
+    fn formats() {
+        let sources = [
+            (Format::Yaml, "module:\n  - name: a\n    deps: [b]\n"),
+            (Format::Toml, "[[module]]\nname = \"a\"\ndeps = [\"b\"]\n"),
+            (Format::Json, "{\"module\": [{\"name\": \"a\", \"deps\": [\"b\"]}]}")
+        ];
+        for &(format, source) in sources.iter() {
+            let config: Config = deserialize(source, format, &index("build", source, format)).unwrap();
+            assert_eq!(config.module[0].name, "a");
+            assert_eq!(config.module[0].deps, Some(vec!("b".to_string())));
+        }
+
+        let e = deserialize::<Config>("module = [", Format::Toml, &index("build.toml", "module = [", Format::Toml));
+        assert!(e.unwrap_err().to_string().contains("build.toml:1:"));
+    }
+
+    #[test]
+    fn numbers() {
+        let source = "module:\n  - name: a\n    path: .\n    config:\n      id: 9007199254740993\n      offset: -9007199254740993\n      large: 18446744073709551615\n      version: 0.1\n".to_string();
+        let mut context = build::Context::new();
+        parse(&mut context, &source, &Options::new()).unwrap();
+
+        let module = context.get_module("a").unwrap();
+        let config = module.config();
+        assert_eq!(config["id"], "9007199254740993");
+        assert_eq!(config["offset"], "-9007199254740993");
+        assert_eq!(config["large"], "18446744073709551615");
+        assert_eq!(config["version"], "0.1");
+        assert_eq!(module.config_value("id"), Some(&ConfigValue::Integer(9007199254740993)));
+    }
+
+    #[test]
+    fn platform_defines() {
+        let source = "module:\n  - name: a\n    path: .\n    when:\n      - compiler: clang\n        config:\n          cmake.flags: {clang: -Weverything}\n".to_string();
+        let parse_with = |defines: &[(&str, &str)]| -> Result<build::Context, Error> {
+            let mut options = Options::new();
+            for (k, v) in defines {
+                options.define(k, v);
+            }
+            let mut context = build::Context::new();
+            parse(&mut context, &source, &options)?;
+            Ok(context)
+        };
+
+        let context = parse_with(&[("platform.compiler", "clang")]).unwrap();
+        let config = context.get_module("a").unwrap().config();
+        assert_eq!(config.get("cmake.flags.clang"), Some(&"-Weverything".to_string()));
+        // Platform overrides are not module config.
+        assert!(config.get("platform.compiler").is_none());
+
+        let context = parse_with(&[("platform.compiler", "gcc")]).unwrap();
+        assert!(context.get_module("a").unwrap().config().get("cmake.flags.clang").is_none());
+
+        assert!(parse_with(&[("platform.vendor", "pc")]).is_err());
+    }
+
+    #[test]
+    fn profiles() {
+        let dir = project("profiles", &[(
+            "build.yml",
+            "default:\n  config:\n    conan.build_type: Debug\n    conan.user: mib\n\
+             profile:\n  release:\n    config:\n      conan.build_type: Release\n\
+             module:\n  - name: a\n"
+        )]);
+        let parse_with = |profile: Option<&str>| -> Result<build::Context, Error> {
+            let mut options = Options::new();
+            options.profile(profile.map(|p| p.to_string()));
+            let mut context = build::Context::new();
+            parse_file(&mut context, &dir.join("build.yml"), &options)?;
+            Ok(context)
+        };
+
+        let context = parse_with(None).unwrap();
+        let module = context.get_module("a").unwrap();
+        assert_eq!(module.config()["conan.build_type"], "Debug");
+        assert_eq!(module.build_dir(), &PathBuf::from("a").join("build"));
+
+        let context = parse_with(Some("release")).unwrap();
+        let module = context.get_module("a").unwrap();
+        assert_eq!(module.config()["conan.build_type"], "Release");
+        assert_eq!(module.config()["conan.user"], "mib");
+        assert_eq!(module.build_dir(), &PathBuf::from("a").join("build-release"));
+        assert_eq!(module.build_dirs(), &vec!(PathBuf::from("a").join("build"), PathBuf::from("a").join("build-release")));
+
+        match parse_with(Some("debug")) {
+            Err(e) => assert!(e.to_string().contains("Unknown profile debug"), "{}", e),
+            Ok(_) => panic!("An unknown profile was accepted.")
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn relative_include() {
+        let dir = project("include", &[
+            ("build.yml", "include: [libs/modules.yml]\nmodule:\n  - name: a\n    deps: [b]\n"),
+            ("libs/modules.yml", "include: [common.yml]\nmodule:\n  - name: b\n"),
+            ("libs/common.yml", "default:\n  config:\n    conan.user: mib\n")
+        ]);
+
+        let context = parse_project(&dir).unwrap();
+        assert_eq!(context.get_module("a").unwrap().module_dir(), &PathBuf::from("a"));
+        // Modules of an included file are relative to it, and so are the files it includes.
+        assert_eq!(context.get_module("b").unwrap().module_dir(), &PathBuf::from("libs").join("b"));
+        assert_eq!(context.get_module("b").unwrap().config()["conan.user"], "mib");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn duplicate_module() {
+        let dir = project("duplicate", &[
+            ("build.yml", "include: [other.yml]\nmodule:\n  - name: a\n"),
+            ("other.yml", "module:\n  - name: a\n    path: other\n")
+        ]);
+
+        let error = parse_error(&dir);
+        assert!(error.contains("Module a is defined more than once."), "{}", error);
+        assert!(error.contains("other.yml:2:"), "{}", error);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn include_cycle() {
+        let dir = project("cycle", &[
+            ("build.yml", "include: [a.yml]\n"),
+            ("a.yml", "include: [b.yml]\n"),
+            ("b.yml", "include: [a.yml]\n")
+        ]);
+
+        let error = parse_error(&dir);
+        assert!(error.contains("Build files include each other"), "{}", error);
+        assert!(error.contains("a.yml -> "), "{}", error);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn discover_nested() {
+        let dir = project("discover", &[
+            ("build.yml", "discover: [libs/**]\n"),
+            ("libs/base/module.yml", "name: base\nconfig:\n  conan.artifact_type: lib\n"),
+            ("libs/net/src/net.cpp", ""),
+            ("libs/net/http/module.toml", "name = \"http\"\ndeps = [\"base\"]\n")
+        ]);
+
+        let context = parse_project(&dir).unwrap();
+        assert_eq!(context.module_names(), vec!("base", "http"));
+
+        let base = context.get_module("base").unwrap();
+        assert_eq!(base.module_dir(), &PathBuf::from("libs").join("base"));
+        assert_eq!(base.config()["conan.artifact_type"], "lib");
+        let http = context.get_module("http").unwrap();
+        assert_eq!(http.module_dir(), &PathBuf::from("libs").join("net").join("http"));
+        assert!(context.get_module_deps("http").unwrap().contains("base"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
\ No newline at end of file
@@ -0,0 +1,206 @@
+use std::env;
+use std::env::consts;
+use std::path::Path;
+
+// Operating systems recognized in target triples, with the name they are matched by.
+const OPERATING_SYSTEMS: &'static [(&'static str, &'static str)] = &[
+    ("linux", "linux"),
+    ("darwin", "macos"),
+    ("macos", "macos"),
+    ("windows", "windows"),
+    ("freebsd", "freebsd"),
+    ("netbsd", "netbsd"),
+    ("openbsd", "openbsd"),
+    ("android", "android"),
+    ("ios", "ios")
+];
+
+// The conditions of a "when" block.
+pub const CONDITIONS: &'static [&'static str] = &["os", "arch", "family", "env", "compiler"];
+
+///
+/// The platform that conditional config is evaluated against.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Platform {
+    os: String,
+    arch: String,
+    family: String,
+    // The ABI or toolchain, for example "gnu" or "msvc". Empty when unknown.
+    env: String,
+    // The C++ compiler, named like the compilers of "cmake.flags": gcc, clang, appleclang or msvc. Empty when unknown.
+    compiler: String
+}
+
+impl Platform {
+    ///
+    /// The platform mib runs on.
+    ///
+    pub fn host() -> Platform {
+        let env = if cfg!(target_env = "msvc") {
+            "msvc"
+        } else if cfg!(target_env = "musl") {
+            "musl"
+        } else if cfg!(target_env = "gnu") {
+            "gnu"
+        } else {
+            ""
+        };
+        Platform {
+            os: consts::OS.to_string(),
+            arch: consts::ARCH.to_string(),
+            family: consts::FAMILY.to_string(),
+            env: env.to_string(),
+            compiler: detect_compiler(consts::OS, env)
+        }
+    }
+
+    ///
+    /// Parses a target triple such as "x86_64-unknown-linux-gnu" or "x86_64-apple-darwin".
+    ///
+    pub fn from_triple(triple: &str) -> Result<Platform, String> {
+        let parts : Vec<&str> = triple.split('-').collect();
+        let os = parts.iter()
+            .enumerate()
+            .skip(1)
+            .filter_map(|(i, part)| OPERATING_SYSTEMS.iter().find(|(name, _)| part == name).map(|(_, os)| (i, *os)))
+            .next();
+
+        match os {
+            Some((i, os)) => {
+                let env = parts.get(i + 1).unwrap_or(&"");
+                Ok(Platform {
+                    os: os.to_string(),
+                    arch: parts[0].to_string(),
+                    family: if os == "windows" { "windows" } else { "unix" }.to_string(),
+                    env: env.to_string(),
+                    compiler: detect_compiler(os, env)
+                })
+            },
+            None => Err(format!("Unable to determine the operating system of target {}.", triple))
+        }
+    }
+
+    ///
+    /// The value of a condition of a "when" block. Returns None for conditions that are not supported.
+    ///
+    pub fn get(&self, condition: &str) -> Option<&String> {
+        match condition {
+            "os" => Some(&self.os),
+            "arch" => Some(&self.arch),
+            "family" => Some(&self.family),
+            "env" => Some(&self.env),
+            "compiler" => Some(&self.compiler),
+            _ => None
+        }
+    }
+
+    ///
+    /// Overrides the value of a condition, for example the detected compiler.
+    ///
+    pub fn set(&mut self, condition: &str, value: &str) -> Result<(), String> {
+        let field = match condition {
+            "os" => &mut self.os,
+            "arch" => &mut self.arch,
+            "family" => &mut self.family,
+            "env" => &mut self.env,
+            "compiler" => &mut self.compiler,
+            _ => return Err(format!("Unknown condition {}. Conditions are {}.", condition, CONDITIONS.join(", ")))
+        };
+        *field = value.to_string();
+        Ok(())
+    }
+}
+
+///
+/// Detects the C++ compiler from the CXX environment variable, which CMake also uses, or else from the usual compiler
+/// of the platform.
+///
+fn detect_compiler(os: &str, env: &str) -> String {
+    if let Some(cxx) = env::var_os("CXX") {
+        if let Some(compiler) = cxx.to_str().and_then(|c| c.split_whitespace().next()) {
+            return compiler_name(compiler, os);
+        }
+    }
+
+    if env == "msvc" {
+        "msvc".to_string()
+    } else if os == "macos" || os == "ios" {
+        "appleclang".to_string()
+    } else if on_path("g++") {
+        "gcc".to_string()
+    } else if on_path("clang++") {
+        "clang".to_string()
+    } else {
+        String::new()
+    }
+}
+
+///
+/// The name of a compiler executable such as "/usr/bin/clang++-14" or "cl.exe" as a compiler condition.
+///
+fn compiler_name(executable: &str, os: &str) -> String {
+    let name = Path::new(executable).file_stem().and_then(|n| n.to_str()).unwrap_or("").to_lowercase();
+    if name.contains("clang") {
+        // The clang of Xcode identifies itself as AppleClang.
+        if os == "macos" || os == "ios" { "appleclang" } else { "clang" }.to_string()
+    } else if name.contains("g++") || name.contains("gcc") {
+        "gcc".to_string()
+    } else if name == "cl" {
+        "msvc".to_string()
+    } else if name == "c++" {
+        if os == "macos" || os == "ios" { "appleclang" } else { "gcc" }.to_string()
+    } else {
+        name
+    }
+}
+
+fn on_path(executable: &str) -> bool {
+    match env::var_os("PATH") {
+        Some(paths) => env::split_paths(&paths).any(|dir| dir.join(executable).is_file()),
+        None => false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn triples() {
+        let linux = Platform::from_triple("x86_64-unknown-linux-gnu").unwrap();
+        assert_eq!(linux.get("os").unwrap(), "linux");
+        assert_eq!(linux.get("arch").unwrap(), "x86_64");
+        assert_eq!(linux.get("family").unwrap(), "unix");
+        assert_eq!(linux.get("env").unwrap(), "gnu");
+
+        let mac = Platform::from_triple("aarch64-apple-darwin").unwrap();
+        assert_eq!(mac.get("os").unwrap(), "macos");
+        assert_eq!(mac.get("env").unwrap(), "");
+
+        let windows = Platform::from_triple("x86_64-pc-windows-msvc").unwrap();
+        assert_eq!(windows.get("family").unwrap(), "windows");
+        assert_eq!(windows.get("env").unwrap(), "msvc");
+
+        assert!(Platform::from_triple("wasm32").is_err());
+        assert!(linux.get("vendor").is_none());
+    }
+
+    #[test]
+    fn compilers() {
+        assert_eq!(compiler_name("/usr/bin/g++-12", "linux"), "gcc");
+        assert_eq!(compiler_name("x86_64-w64-mingw32-g++", "windows"), "gcc");
+        assert_eq!(compiler_name("/usr/bin/clang++-14", "linux"), "clang");
+        assert_eq!(compiler_name("/usr/bin/clang++", "macos"), "appleclang");
+        assert_eq!(compiler_name("cl.exe", "windows"), "msvc");
+        assert_eq!(compiler_name("c++", "linux"), "gcc");
+        assert_eq!(compiler_name("icpx", "linux"), "icpx");
+
+        let mut linux = Platform::from_triple("x86_64-unknown-linux-gnu").unwrap();
+        assert!(linux.get("compiler").is_some());
+        linux.set("compiler", "clang").unwrap();
+        assert_eq!(linux.get("compiler").unwrap(), "clang");
+        assert!(linux.set("vendor", "pc").is_err());
+    }
+}
\ No newline at end of file
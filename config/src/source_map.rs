@@ -0,0 +1,167 @@
+use build::Location;
+use std::collections::HashMap;
+use yaml_rust::parser::Event;
+use yaml_rust::parser::MarkedEventReceiver;
+use yaml_rust::parser::Parser;
+use yaml_rust::scanner::Marker;
+
+///
+/// The positions of the keys and sequence items of a YAML document.
+///
+/// Positions are looked up by path: the keys and sequence indices leading to a node joined with dots, for example
+/// "module.1.deps.0" for the first dependency of the second module. Map entries are located at their key.
+///
+pub struct SourceMap {
+    file: String,
+    source: String,
+    positions: HashMap<String, (usize, usize)>
+}
+
+enum Frame {
+    // Block mappings start after their first key so items that are mappings are moved to the first key.
+    Map { path: String, key: Option<String>, first_key: bool },
+    Sequence { path: String, index: usize }
+}
+
+struct Recorder {
+    stack: Vec<Frame>,
+    positions: HashMap<String, (usize, usize)>
+}
+
+impl SourceMap {
+    ///
+    /// Indexes the source. Invalid YAML results in a map that only knows about the nodes before the error.
+    ///
+    pub fn new(file: &str, source: &str) -> SourceMap {
+        let mut recorder = Recorder {
+            stack: vec!(),
+            positions: HashMap::new()
+        };
+        if let Err(e) = Parser::new(source.chars()).load(&mut recorder, false) {
+            debug!("Unable to index {}: {}", file, e);
+        }
+
+        SourceMap {
+            file: file.to_string(),
+            source: source.to_string(),
+            positions: recorder.positions
+        }
+    }
+
+    ///
+    /// A map without positions for sources that are not YAML.
+    ///
+    pub fn unindexed(file: &str, source: &str) -> SourceMap {
+        SourceMap {
+            file: file.to_string(),
+            source: source.to_string(),
+            positions: HashMap::new()
+        }
+    }
+
+    pub fn file(&self) -> &String {
+        &self.file
+    }
+
+    pub fn location(&self, path: &str) -> Option<Location> {
+        self.positions.get(path).map(|&(line, column)| self.location_at(line, column))
+    }
+
+    pub fn location_at(&self, line: usize, column: usize) -> Location {
+        Location::new(&self.file, &self.source, line, column)
+    }
+
+    ///
+    /// The locations of every node below the path, keyed by their path relative to it.
+    ///
+    pub fn locations_below(&self, path: &str) -> Vec<(String, Location)> {
+        let prefix = format!("{}.", path);
+        self.positions
+            .keys()
+            .filter(|p| p.starts_with(&prefix))
+            .map(|p| (p[prefix.len()..].to_string(), self.location(p).unwrap()))
+            .collect()
+    }
+}
+
+impl Recorder {
+    ///
+    /// Records the position of a node and returns its path.
+    ///
+    fn node(&mut self, scalar: Option<String>, mark: Marker) -> String {
+        // Lines start at 1 but columns at 0.
+        let position = (mark.line(), mark.col() + 1);
+        let join = |path: &String, segment: &str| {
+            if path.is_empty() { segment.to_string() } else { format!("{}.{}", path, segment) }
+        };
+
+        match self.stack.last_mut() {
+            None => String::new(),
+            Some(Frame::Map { path, key, first_key }) => match key.take() {
+                Some(k) => join(path, &k),
+                None => {
+                    // The node is a key. Keys that are not scalars can not be referred to.
+                    let k = scalar.unwrap_or_default();
+                    self.positions.insert(join(path, &k), position);
+                    if *first_key && self.positions.contains_key(path.as_str()) {
+                        self.positions.insert(path.clone(), position);
+                    }
+                    *first_key = false;
+                    *key = Some(k);
+                    String::new()
+                }
+            },
+            Some(Frame::Sequence { path, index }) => {
+                let item = join(path, &index.to_string());
+                *index = *index + 1;
+                self.positions.insert(item.clone(), position);
+                item
+            }
+        }
+    }
+}
+
+impl MarkedEventReceiver for Recorder {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        match ev {
+            Event::Scalar(value, _, _, _) => { self.node(Some(value), mark); },
+            Event::Alias(_) => { self.node(None, mark); },
+            Event::MappingStart(_) => {
+                let path = self.node(None, mark);
+                self.stack.push(Frame::Map { path: path, key: None, first_key: true });
+            },
+            Event::SequenceStart(_) => {
+                let path = self.node(None, mark);
+                self.stack.push(Frame::Sequence { path: path, index: 0 });
+            },
+            Event::MappingEnd | Event::SequenceEnd => { self.stack.pop(); },
+            _ => ()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn positions() {
+        let source = "default:\n  config:\n    conan.user: me\nmodule:\n  - name: a\n    deps:\n      - b\n";
+        let map = SourceMap::new("build.yml", source);
+
+        let location = |path| {
+            let l = map.location(path).unwrap();
+            (l.line(), l.column())
+        };
+        assert_eq!(location("default.config.conan.user"), (3, 5));
+        assert_eq!(location("module.0"), (5, 5));
+        assert_eq!(location("module.0.name"), (5, 5));
+        assert_eq!(location("module.0.deps.0"), (7, 9));
+        assert!(map.location("module.1").is_none());
+
+        let mut below : Vec<String> = map.locations_below("module.0").into_iter().map(|(p, _)| p).collect();
+        below.sort();
+        assert_eq!(below, vec!("deps", "deps.0", "name"));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,186 @@
+use build::Context;
+use build::Executor;
+use failure::Error;
+use serde_json;
+use std::collections::BTreeSet;
+use std::collections::HashSet;
+
+pub const STATE_OUT_OF_DATE: &'static str = "out of date";
+pub const STATE_UP_TO_DATE: &'static str = "up to date";
+pub const STATE_UNKNOWN: &'static str = "unknown";
+
+///
+/// The modules and tasks of a project and the dependencies between them.
+///
+#[derive(Serialize)]
+pub struct Graph {
+    modules: Vec<ModuleNode>,
+    tasks: Vec<TaskNode>
+}
+
+#[derive(Serialize)]
+struct ModuleNode {
+    name: String,
+    path: String,
+    types: Vec<String>,
+    deps: Vec<String>
+}
+
+#[derive(Serialize)]
+struct TaskNode {
+    name: String,
+    module: Option<String>,
+    state: String,
+    deps: Vec<String>
+}
+
+impl Graph {
+    ///
+    /// Builds the graph of everything the targets depend on, or of the whole project if there are no targets. The
+    /// executor is used to determine which tasks are out of date without executing them.
+    ///
+    pub fn new(context: &Context, executor: &mut Executor, targets: &[String]) -> Result<Graph, Error> {
+        let mut tasks : BTreeSet<String> = BTreeSet::new();
+        for target in targets {
+            tasks.append(&mut closure(target, |t| context.get_task_deps(t))?);
+        }
+        if targets.is_empty() {
+            tasks = context.task_names().into_iter().cloned().collect();
+        }
+
+        // Only modules owning one of the tasks and the modules those depend on are part of a target's graph.
+        let modules : BTreeSet<String> = match targets.is_empty() {
+            false => {
+                let mut modules = BTreeSet::new();
+                for task in &tasks {
+                    if let Some(m) = context.task_module(task) {
+                        modules.append(&mut closure(m.name(), |m| context.get_module_deps(m))?);
+                    }
+                }
+                modules
+            },
+            true => context.module_names().into_iter().cloned().collect()
+        };
+
+        executor.dry_run(true).keep_going(true);
+        for task in &tasks {
+            if let Err(e) = executor.execute(task) {
+                warn!("Unable to determine the state of {}: {}", task, e);
+            }
+        }
+        let summary = executor.summary();
+
+        let mut graph = Graph {
+            modules: vec!(),
+            tasks: vec!()
+        };
+
+        for name in modules {
+            let module = context.get_module(&name)?;
+            graph.modules.push(ModuleNode {
+                name: name.clone(),
+                path: module.module_dir().display().to_string(),
+                types: sorted(module.types().iter()),
+                deps: sorted(context.get_module_deps(&name)?.iter())
+            });
+        }
+
+        for name in tasks {
+            let state = if summary.executed().contains(&name) {
+                STATE_OUT_OF_DATE
+            } else if summary.up_to_date().contains(&name) {
+                STATE_UP_TO_DATE
+            } else {
+                STATE_UNKNOWN
+            };
+            graph.tasks.push(TaskNode {
+                module: context.task_module(&name).map(|m| m.name().clone()),
+                state: state.to_string(),
+                deps: sorted(context.get_task_deps(&name)?.iter()),
+                name: name
+            });
+        }
+
+        Ok(graph)
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    ///
+    /// Renders the graph in the Graphviz DOT language. Edges point from a dependent to its dependency.
+    ///
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        dot.push_str("digraph mib {\n");
+        dot.push_str("    rankdir=LR;\n");
+
+        dot.push_str("    subgraph cluster_modules {\n");
+        dot.push_str("        label=\"modules\";\n");
+        for m in &self.modules {
+            dot.push_str(&format!(
+                "        {} [shape=box, label={}];\n",
+                quote(&format!("module {}", m.name)),
+                quote(&format!("{}\n({})", m.name, m.types.join(", ")))
+            ));
+            for dep in &m.deps {
+                dot.push_str(&format!("        {} -> {};\n", quote(&format!("module {}", m.name)), quote(&format!("module {}", dep))));
+            }
+        }
+        dot.push_str("    }\n");
+
+        dot.push_str("    subgraph cluster_tasks {\n");
+        dot.push_str("        label=\"tasks\";\n");
+        for t in &self.tasks {
+            let color = match t.state.as_str() {
+                STATE_OUT_OF_DATE => "lightsalmon",
+                STATE_UP_TO_DATE => "palegreen",
+                _ => "lightgrey"
+            };
+            dot.push_str(&format!(
+                "        {} [style=filled, fillcolor={}, label={}];\n",
+                quote(&format!("task {}", t.name)),
+                color,
+                quote(&format!("{}\n{}", t.name, t.state))
+            ));
+            for dep in &t.deps {
+                dot.push_str(&format!("        {} -> {};\n", quote(&format!("task {}", t.name)), quote(&format!("task {}", dep))));
+            }
+        }
+        dot.push_str("    }\n");
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+///
+/// The item and everything it depends on, directly or not.
+///
+fn closure<'a, F>(item: &str, deps: F) -> Result<BTreeSet<String>, Error>
+    where F: Fn(&str) -> Result<&'a HashSet<String>, Error>
+{
+    let mut items = BTreeSet::new();
+    let mut stack = vec!(item.to_string());
+    while let Some(item) = stack.pop() {
+        if items.contains(&item) {
+            continue;
+        }
+        for dep in deps(&item)? {
+            stack.push(dep.clone());
+        }
+        items.insert(item);
+    }
+    Ok(items)
+}
+
+pub fn sorted<'a, I: Iterator<Item=&'a String>>(items: I) -> Vec<String> {
+    let mut items : Vec<String> = items.cloned().collect();
+    items.sort();
+    items
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace("\\", "\\\\").replace("\"", "\\\"").replace("\n", "\\n"))
+}
\ No newline at end of file
@@ -0,0 +1,98 @@
+use build::Context;
+use failure::Error;
+use graph::sorted;
+use serde::Serialize;
+use serde_json;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+///
+/// A task as listed by the "tasks" command.
+///
+#[derive(Serialize)]
+pub struct TaskListing {
+    name: String,
+    plugin: Option<String>,
+    deps: Vec<String>
+}
+
+///
+/// A module as listed by the "modules" command.
+///
+#[derive(Serialize)]
+pub struct ModuleListing {
+    name: String,
+    path: String,
+    types: Vec<String>,
+    build_dir: String,
+    deps: Vec<String>,
+    config: BTreeMap<String, String>
+}
+
+///
+/// Lists every task ordered by name. The plugins map each task to the name of the plugin that added it.
+///
+pub fn tasks(context: &Context, plugins: &HashMap<String, String>) -> Result<Vec<TaskListing>, Error> {
+    let mut tasks = vec!();
+    for name in context.task_names() {
+        tasks.push(TaskListing {
+            name: name.clone(),
+            plugin: plugins.get(name).cloned(),
+            deps: sorted(context.get_task_deps(name)?.iter())
+        });
+    }
+    Ok(tasks)
+}
+
+///
+/// Lists every module ordered by name.
+///
+pub fn modules(context: &Context) -> Result<Vec<ModuleListing>, Error> {
+    let mut modules = vec!();
+    for name in context.module_names() {
+        let module = context.get_module(name)?;
+        modules.push(ModuleListing {
+            name: name.clone(),
+            path: module.module_dir().display().to_string(),
+            types: sorted(module.types().iter()),
+            build_dir: module.build_dir().display().to_string(),
+            deps: sorted(context.get_module_deps(name)?.iter()),
+            config: module.config().iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+        });
+    }
+    Ok(modules)
+}
+
+pub fn tasks_to_text(tasks: &Vec<TaskListing>) -> String {
+    let mut text = String::new();
+    for task in tasks {
+        match &task.plugin {
+            Some(plugin) => text.push_str(&format!("{} (from {})\n", task.name, plugin)),
+            None => text.push_str(&format!("{}\n", task.name))
+        }
+        for dep in &task.deps {
+            text.push_str(&format!("\tdepends on {}\n", dep));
+        }
+    }
+    text
+}
+
+pub fn modules_to_text(modules: &Vec<ModuleListing>) -> String {
+    let mut text = String::new();
+    for module in modules {
+        text.push_str(&format!("{}\n", module.name));
+        text.push_str(&format!("\tpath: {}\n", module.path));
+        text.push_str(&format!("\ttypes: {}\n", module.types.join(", ")));
+        text.push_str(&format!("\tbuild_dir: {}\n", module.build_dir));
+        text.push_str(&format!("\tdeps: {}\n", module.deps.join(", ")));
+        text.push_str("\tconfig:\n");
+        for (k, v) in &module.config {
+            text.push_str(&format!("\t\t{}: {}\n", k, v));
+        }
+    }
+    text
+}
+
+pub fn to_json<T: Serialize>(listing: &Vec<T>) -> Result<String, Error> {
+    Ok(serde_json::to_string_pretty(listing)?)
+}
\ No newline at end of file
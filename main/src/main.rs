@@ -2,27 +2,71 @@ extern crate build;
 extern crate config;
 extern crate failure;
 extern crate plugin;
+extern crate serde;
+extern crate serde_json;
 extern crate simple_logger;
 
 #[macro_use] extern crate log;
 #[macro_use] extern crate serde_derive;
 
+mod graph;
+mod listing;
 mod opt;
 
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
 use std::path::PathBuf;
 use std::env;
 use std::fs;
+use std::process;
 
 pub const PLUGIN_DIR_NAME: &'static str = "plugins";
+pub const TEMPLATES_DIR_NAME: &'static str = "templates";
+pub const STATE_DIR_NAME: &'static str = ".mib";
+pub const FINGERPRINT_FILE_NAME: &'static str = "fingerprints";
 pub const DEFAULT_BUILD_TASK_NAME: &'static str = "build";
 pub const DEFAULT_CLEAN_TASK_NAME: &'static str = "clean";
+pub const DEFAULT_TEST_TASK_NAME: &'static str = "test";
+pub const GRAPH_COMMAND: &'static str = "graph";
+pub const TASKS_COMMAND: &'static str = "tasks";
+pub const MODULES_COMMAND: &'static str = "modules";
+pub const BUILTIN_PLUGIN_NAME: &'static str = "mib";
+// Targets starting with this refer to the project rather than to the module mib was started in.
+pub const ROOT_TARGET_PREFIX: &'static str = "//";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Execute,
+    Graph,
+    Tasks,
+    Modules
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Text,
+    Dot,
+    Json
+}
 
 #[derive(Debug)]
 struct CmdLineOptions {
     config_dir: Option<PathBuf>,
     buildfile: Option<PathBuf>,
     root_dir: Option<PathBuf>,
-    target: Option<String>,
+    command: Command,
+    // Selectors of the tasks to execute, see `Context::select`.
+    targets: Vec<String>,
+    format: OutputFormat,
+    jobs: Option<usize>,
+    keep_going: bool,
+    dry_run: bool,
+    profile: Option<String>,
+    // The target triple that platform conditional config is evaluated against.
+    triple: Option<String>,
+    // Config keys set with -D.
+    defines: Vec<(String, String)>,
     display_help: Option<Option<String>>, // Optional value specifies a module whose help must be displayed.
     log_level: log::Level
 }
@@ -33,13 +77,34 @@ impl CmdLineOptions {
             config_dir: None,
             buildfile: None,
             root_dir: None,
-            target: None,
+            command: Command::Execute,
+            targets: vec!(),
+            format: OutputFormat::Text,
+            jobs: None,
+            keep_going: false,
+            dry_run: false,
+            profile: None,
+            triple: None,
+            defines: vec!(),
             display_help: None,
             log_level: log::Level::Info
         }
     }
 }
 
+///
+/// The exit status of mib for each class of failure.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExitCode {
+    Success = 0,
+    TaskFailed = 1,
+    Usage = 2,
+    Config = 3,
+    Plugin = 4,
+    NoSuchTask = 5
+}
+
 struct EmptyTask;
 impl build::Task for EmptyTask {
     fn needs_execution(&self) -> bool {
@@ -52,24 +117,79 @@ impl build::Task for EmptyTask {
 
 ///
 /// Search this path or the working directory if not specified and all parent paths for a build file.
-/// 
+///
 fn get_default_build_file(root: &Option<PathBuf>) -> Option<PathBuf> {
-    let default_names = ["build.yml", "build.yaml"];
+    let default_names = ["build.yml", "build.yaml", "build.toml", "build.json"];
 
     let path = match root.as_ref() {
         Some(path) => path.clone(),
         None => PathBuf::from(".")
     };
+    let path = match fs::canonicalize(&path) {
+        Ok(p) => p,
+        Err(_) => return None
+    };
 
-    for name in default_names.iter() {
-        let mut file = path.clone();
-        file.push(name);
-        if file.exists() { return Some(file); }
+    for dir in path.ancestors() {
+        for name in default_names.iter() {
+            let file = dir.join(name);
+            if file.exists() { return Some(file); }
+        }
     }
 
     return None;
 }
 
+///
+/// The module whose folder contains the directory, the innermost one if modules are nested.
+///
+fn get_enclosing_module(context: &build::Context, dir: &Path) -> Option<String> {
+    let mut enclosing : Option<(String, PathBuf)> = None;
+    for name in context.module_names() {
+        let module_dir = match context.get_module(name).ok().and_then(|m| fs::canonicalize(m.module_dir()).ok()) {
+            Some(d) => d,
+            None => continue
+        };
+        let deeper = match &enclosing {
+            Some((_, d)) => module_dir.starts_with(d),
+            None => true
+        };
+        if dir.starts_with(&module_dir) && deeper {
+            enclosing = Some((name.clone(), module_dir));
+        }
+    }
+    enclosing.map(|(name, _)| name)
+}
+
+///
+/// The selectors to try for a target, see `Context::select`. Targets without a module, such as "build", refer to the
+/// task of the module mib was started in, if any, and otherwise to the task of the project. "//" selects the task of
+/// the project explicitly, for example "//build".
+///
+fn scope_target(target: &str, module: &Option<String>) -> Vec<String> {
+    if target.starts_with(ROOT_TARGET_PREFIX) {
+        return vec!(target[ROOT_TARGET_PREFIX.len()..].to_string());
+    }
+    let (prefix, name) = match target.starts_with('^') {
+        true => ("^", &target[1..]),
+        false => ("", target)
+    };
+    match module {
+        Some(m) if !name.contains(':') => vec!(format!("{}{}:{}", prefix, m, name), target.to_string()),
+        _ => vec!(target.to_string())
+    }
+}
+
+///
+/// The project root is the directory containing the build file.
+///
+fn get_project_root(buildfile: &Path) -> PathBuf {
+    match buildfile.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+        _ => PathBuf::from(".")
+    }
+}
+
 fn get_default_config_dir() -> Result<PathBuf, String> {
     let path_buf = match env::home_dir() {
         Some(p) => { let mut p = p.clone(); p.push(".builder"); p },
@@ -90,6 +210,10 @@ fn get_default_config_dir() -> Result<PathBuf, String> {
 }
 
 fn main() {
+    process::exit(run() as i32);
+}
+
+fn run() -> ExitCode {
     let args : Vec<String> = std::env::args().collect();
 
     let mut opt_parser : opt::OptParser<CmdLineOptions> = opt::OptParser::new(&args[0]);
@@ -140,7 +264,7 @@ fn main() {
             |_, cmdline_options, args| {
                 match args.pop() {
                     Some(s) => {
-                        let mut p = PathBuf::from(s);
+                        let p = PathBuf::from(s);
                         if !p.is_dir() {
                             return Err(format!("Root path {} is not a directory.", p.display()))
                         }
@@ -153,6 +277,96 @@ fn main() {
                 }
             }
         )
+        .opt("--jobs", "Maximum number of tasks to execute in parallel. (Default: 1)",
+            |_, cmdline_options, args| {
+                match args.pop() {
+                    Some(s) => {
+                        match s.parse::<usize>() {
+                            Ok(n) if n > 0 => {
+                                cmdline_options.jobs = Some(n);
+                                return Ok(())
+                            },
+                            _ => return Err(format!("\"--jobs\" expects a positive number but got {}.", s))
+                        }
+                    },
+                    None => {
+                        return Err("\"--jobs\" expects a number.".to_string())
+                    }
+                }
+            }
+        )
+        .opt("--keep-going", "Keep executing tasks that do not depend on a failed task and print a summary at the end.",
+            |_, cmdline_options, _| {
+                cmdline_options.keep_going = true;
+                Ok(())
+            }
+        )
+        .opt("--dry-run", "Print the tasks that would execute and why without executing them.",
+            |_, cmdline_options, _| {
+                cmdline_options.dry_run = true;
+                Ok(())
+            }
+        )
+        .opt("--profile", "Name of a profile in the build file whose config is applied to every module.",
+            |_, cmdline_options, args| {
+                match args.pop() {
+                    Some(s) => {
+                        cmdline_options.profile = Some(s);
+                        return Ok(())
+                    },
+                    None => {
+                        return Err("\"--profile\" expects a name.".to_string())
+                    }
+                }
+            }
+        )
+        .opt("--triple", "Target triple, such as x86_64-pc-windows-msvc, that platform conditional config is evaluated against. (Default: the host)",
+            |_, cmdline_options, args| {
+                match args.pop() {
+                    Some(s) => {
+                        cmdline_options.triple = Some(s);
+                        return Ok(())
+                    },
+                    None => {
+                        return Err("\"--triple\" expects a target triple.".to_string())
+                    }
+                }
+            }
+        )
+        .opt("-D", "Sets a config key of every module, for example \"-D conan.channel=testing\". \"-D platform.compiler=clang\" overrides a platform condition instead.",
+            |_, cmdline_options, args| {
+                match args.pop() {
+                    Some(s) => match s.find('=') {
+                        Some(idx) if idx > 0 => {
+                            cmdline_options.defines.push((s[..idx].to_string(), s[idx + 1..].to_string()));
+                            return Ok(())
+                        },
+                        _ => return Err(format!("\"-D\" expects key=value but got {}.", s))
+                    },
+                    None => {
+                        return Err("\"-D\" expects key=value.".to_string())
+                    }
+                }
+            }
+        )
+        .opt("--format", "Output format of commands that print information: \"text\", \"dot\" or \"json\".",
+            |_, cmdline_options, args| {
+                match args.pop() {
+                    Some(s) => {
+                        cmdline_options.format = match s.as_str() {
+                            "text" => OutputFormat::Text,
+                            "dot" => OutputFormat::Dot,
+                            "json" => OutputFormat::Json,
+                            _ => return Err(format!("\"--format\" expects one of text, dot or json but got {}.", s))
+                        };
+                        return Ok(())
+                    },
+                    None => {
+                        return Err("\"--format\" expects a format.".to_string())
+                    }
+                }
+            }
+        )
         .opt("--help", "Print this usage.",
             |_, cmdline_options, args| {
                 cmdline_options.display_help = Some(args.pop());
@@ -165,16 +379,23 @@ fn main() {
     let command_args = match opt_parser.parse(&mut cmdline_options, &args) {
         Ok(args) => args,
         Err(e) => {
-            error!("Error: {}", e);
+            eprintln!("Error: {}", e);
             opt_parser.print_usage();
-            return;
+            return ExitCode::Usage;
         }
     };
 
     simple_logger::init_with_level(cmdline_options.log_level).unwrap();
 
-    if command_args.len() > 0 {
-        cmdline_options.target = Some(command_args[0].clone());
+    match command_args.first().map(|c| c.as_str()) {
+        Some(GRAPH_COMMAND) => {
+            cmdline_options.command = Command::Graph;
+            cmdline_options.targets = command_args[1..].to_vec();
+        },
+        Some(TASKS_COMMAND) => cmdline_options.command = Command::Tasks,
+        Some(MODULES_COMMAND) => cmdline_options.command = Command::Modules,
+        Some(_) => cmdline_options.targets = command_args.clone(),
+        None => ()
     }
 
     //
@@ -182,24 +403,32 @@ fn main() {
     //
     if Some(None) == cmdline_options.display_help {
         opt_parser.print_usage();
-        return;
+        return ExitCode::Success;
     }
 
     // We need a context to display usage of individual builders.
     if cmdline_options.config_dir == None {
-        cmdline_options.config_dir = Some(get_default_config_dir().expect("No config dir specified and no default found."))
+        match get_default_config_dir() {
+            Ok(dir) => cmdline_options.config_dir = Some(dir),
+            Err(e) => {
+                report_error("No config dir specified and no default found.", &[e]);
+                return ExitCode::Usage;
+            }
+        }
     }
 
     // Load plugins.
     let mut plugins_dir = cmdline_options.config_dir.as_ref().unwrap().clone();
     plugins_dir.push(PLUGIN_DIR_NAME);
-    let plugins = plugin::Framework::new(&plugins_dir);
+    let templates_dir = cmdline_options.config_dir.as_ref().unwrap().join(TEMPLATES_DIR_NAME);
+    let plugins = plugin::Framework::new(&plugins_dir, &templates_dir);
 
     let plugins = match plugins.get_plugins() {
         Ok(p) => p,
-        Err(e) => {
-            error!("Error retrieving plugins: {:?}", e);
-            vec!()
+        Err(errors) => {
+            let causes : Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+            report_error("Unable to load plugins.", &causes);
+            return ExitCode::Plugin;
         }
     };
 
@@ -223,46 +452,304 @@ fn main() {
         cmdline_options.buildfile = get_default_build_file(&cmdline_options.root_dir)
     }
     if &cmdline_options.buildfile == &None {
-        error!("Unable to find buildfile.");
-        return;
+        report_error("Unable to find buildfile.", &[]);
+        return ExitCode::Config;
     }
 
+    // Work from the project root so that paths in the build file are relative to it wherever mib was started. The
+    // directory mib was started in decides the module of targets without one.
+    let invocation_dir = env::current_dir().and_then(|d| fs::canonicalize(d));
+    let buildfile = match fs::canonicalize(cmdline_options.buildfile.as_ref().unwrap()) {
+        Ok(f) => f,
+        Err(e) => {
+            report_error(&format!("Unable to find buildfile {}.", cmdline_options.buildfile.as_ref().unwrap().display()), &[e.to_string()]);
+            return ExitCode::Config;
+        }
+    };
+    let project_root = get_project_root(&buildfile);
+    if let Err(e) = env::set_current_dir(&project_root) {
+        report_error("Unable to change to the project root.", &[e.to_string()]);
+        return ExitCode::Config;
+    }
+    cmdline_options.buildfile = buildfile.file_name().map(PathBuf::from);
+
     // Create context.
     let mut context = build::Context::new();
     context.add_task(DEFAULT_BUILD_TASK_NAME, Box::new(EmptyTask));
     context.add_task(DEFAULT_CLEAN_TASK_NAME, Box::new(EmptyTask));
+    context.add_task(DEFAULT_TEST_TASK_NAME, Box::new(EmptyTask));
 
     // Parse build file.
     let buildfile = cmdline_options.buildfile.as_ref().unwrap().as_path();
-    if let Err(e) = match buildfile.extension() {
-        Some(osstr) => {
-            if      osstr == "yml"  { config::parse_file(&mut context, buildfile) }
-            else if osstr == "yaml" { config::parse_file(&mut context, buildfile) }
-            else { panic!("Unable to determine format from buildfile extension.".to_string()) }
-        },
-        None => panic!("Buildfile has no extension. Unable to determine format.".to_string())
-    } {
-        error!("Error parsing document {}: {}", buildfile.display(), e);
+    let mut options = config::Options::new();
+    options.profile(cmdline_options.profile.clone());
+    options.triple(cmdline_options.triple.clone());
+    for (key, value) in &cmdline_options.defines {
+        options.define(key, value);
+    }
+    if let Err(e) = config::parse_file(&mut context, buildfile, &options) {
+        report_error(&format!("Error parsing document {}.", buildfile.display()), &causes(&e));
+        return ExitCode::Config;
     }
 
-    // Generate tasks.
+    // Generate tasks and remember which plugin added each of them.
+    let mut task_plugins : HashMap<String, String> = HashMap::new();
+    for task in context.task_names() {
+        task_plugins.insert(task.clone(), BUILTIN_PLUGIN_NAME.to_string());
+    }
     for plugin in plugins {
+        let existing : HashSet<String> = context.task_names().into_iter().cloned().collect();
         if let Err(e) = plugin.configure(&mut context) {
-            error!("Error applying plugin: {}", e)
+            report_error(&format!("Error applying plugin {}.", plugin.name()), &causes(&e));
+            return ExitCode::Plugin;
+        }
+        for task in context.task_names() {
+            if !existing.contains(task) {
+                task_plugins.insert(task.clone(), plugin.name());
+            }
+        }
+    }
+
+    // Reject tasks that depend on each other.
+    if let Err(e) = context.finalize() {
+        report_error("Error in dependency graph.", &causes(&e));
+        return ExitCode::Config;
+    }
+
+    // Resolve the selected targets into task names.
+    // Modules at the project root, like "path: .", do not take over targets started at the root.
+    let module = match &invocation_dir {
+        Ok(dir) if *dir != project_root => get_enclosing_module(&context, dir),
+        _ => None
+    };
+    if let Some(m) = &module {
+        debug!("Targets without a module refer to module {}.", m);
+    }
+    let mut selectors = cmdline_options.targets.clone();
+    if selectors.is_empty() && cmdline_options.command == Command::Execute {
+        selectors.push(DEFAULT_BUILD_TASK_NAME.to_string());
+    }
+    let mut targets : Vec<String> = vec!();
+    for selector in &selectors {
+        // Use the first candidate that selects anything.
+        let mut selected : Result<Vec<String>, failure::Error> = Err(build::BuildError::NoSuchTask(format!("No task matches {}.", selector)).into());
+        for candidate in scope_target(selector, &module) {
+            selected = context.select(&candidate);
+            if selected.is_ok() {
+                break;
+            }
+        }
+        match selected {
+            Ok(tasks) => for task in tasks {
+                if !targets.contains(&task) {
+                    targets.push(task);
+                }
+            },
+            Err(e) => {
+                report_error(&format!("Unable to find {}.", selector), &causes(&e));
+                return ExitCode::NoSuchTask;
+            }
         }
     }
 
-    // Execute build.
     let mut executor = build::Executor::new(&context);
-    if let Some(t) = cmdline_options.target {
-        if let Err(e) = executor.execute(&t) {
-            error!("Error executing task: {}", e);
-            return;
+    if let Some(jobs) = cmdline_options.jobs {
+        executor.jobs(jobs);
+    }
+    executor.keep_going(cmdline_options.keep_going);
+    executor.dry_run(cmdline_options.dry_run);
+
+    // Load the fingerprints of previously executed tasks from the project root. Profiles build in separate
+    // directories so each has its own fingerprints.
+    let fingerprint_file = match &cmdline_options.profile {
+        Some(p) => get_project_root(buildfile).join(STATE_DIR_NAME).join(format!("{}-{}", FINGERPRINT_FILE_NAME, p)),
+        None => get_project_root(buildfile).join(STATE_DIR_NAME).join(FINGERPRINT_FILE_NAME)
+    };
+    match build::FingerprintStore::load(&fingerprint_file) {
+        Ok(store) => { executor.fingerprints(store); },
+        Err(e) => warn!("Ignoring fingerprints: {}", e)
+    }
+
+    match cmdline_options.command {
+        Command::Graph => return print_graph(&context, &mut executor, &targets, cmdline_options.format),
+        Command::Tasks => return print_listing(
+            listing::tasks(&context, &task_plugins),
+            listing::tasks_to_text,
+            cmdline_options.format
+        ),
+        Command::Modules => return print_listing(
+            listing::modules(&context),
+            listing::modules_to_text,
+            cmdline_options.format
+        ),
+        Command::Execute => ()
+    }
+
+    // Execute build. All targets are executed together so that shared dependencies are only considered once.
+    let result = executor.execute_all(&targets);
+
+    if cmdline_options.dry_run {
+        print_decisions(executor.decisions());
+    }
+
+    if cmdline_options.keep_going {
+        print_summary(&executor.summary());
+    }
+
+    if let Err(e) = result {
+        let failed = executor.summary().failed().clone();
+        if failed.is_empty() {
+            report_error(&format!("Error executing {}.", targets.join(", ")), &causes(&e));
+        }
+        for task in failed {
+            let title = match context.task_module(&task) {
+                Some(m) => format!("Task {} of module {} ({}) failed.", task, m.name(), m.module_dir().display()),
+                None => format!("Task {} failed.", task)
+            };
+            let causes = match executor.error(&task) {
+                Some(e) => causes(e),
+                None => vec!()
+            };
+            report_error(&title, &causes);
+        }
+        return ExitCode::TaskFailed;
+    }
+
+    ExitCode::Success
+}
+
+///
+/// The messages of an error and of every error that caused it.
+///
+fn causes(e: &failure::Error) -> Vec<String> {
+    e.iter_chain().map(|cause| cause.to_string()).collect()
+}
+
+fn report_error(title: &str, causes: &[String]) {
+    eprintln!();
+    eprintln!("error: {}", title);
+    for cause in causes {
+        // Keep multi-line causes, such as source snippets, indented below the cause.
+        eprintln!("  caused by: {}", cause.replace("\n", "\n    "));
+    }
+}
+
+fn print_graph(context: &build::Context, executor: &mut build::Executor, targets: &[String], format: OutputFormat) -> ExitCode {
+    let graph = match graph::Graph::new(context, executor, targets) {
+        Ok(g) => g,
+        Err(e) => {
+            report_error("Unable to create graph.", &causes(&e));
+            return ExitCode::Config;
+        }
+    };
+
+    match format {
+        OutputFormat::Text | OutputFormat::Dot => print!("{}", graph.to_dot()),
+        OutputFormat::Json => match graph.to_json() {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                report_error("Unable to write graph.", &causes(&e));
+                return ExitCode::Config;
+            }
+        }
+    }
+
+    ExitCode::Success
+}
+
+fn print_listing<T: serde::Serialize>(
+    listing: Result<Vec<T>, failure::Error>,
+    to_text: fn(&Vec<T>) -> String,
+    format: OutputFormat
+) -> ExitCode {
+    let output = match format {
+        OutputFormat::Text => listing.map(|l| to_text(&l)),
+        OutputFormat::Json => listing.and_then(|l| listing::to_json(&l)),
+        OutputFormat::Dot => {
+            report_error("The dot format is only supported by the graph command.", &[]);
+            return ExitCode::Usage;
+        }
+    };
+
+    match output {
+        Ok(output) => {
+            println!("{}", output.trim_end());
+            ExitCode::Success
+        },
+        Err(e) => {
+            report_error("Unable to list.", &causes(&e));
+            ExitCode::Config
         }
-    } else {
-        if let Err(e) = executor.execute(DEFAULT_BUILD_TASK_NAME) {
-            error!("Error building project: {}", e);
-            return;
+    }
+}
+
+fn print_decisions(decisions: &Vec<(String, build::ExecutionReason)>) {
+    for (task, reason) in decisions {
+        match reason {
+            build::ExecutionReason::UpToDate => println!("{}: {}", task, reason),
+            _ => println!("{}: would execute ({})", task, reason)
         }
     }
 }
+
+fn print_summary(summary: &build::ExecutionSummary) {
+    let print = |title: &str, tasks: &Vec<String>| {
+        println!("{} ({}):", title, tasks.len());
+        for task in tasks {
+            println!("\t{}", task);
+        }
+    };
+
+    println!();
+    print("Succeeded", summary.executed());
+    print("Up to date", summary.up_to_date());
+    print("Skipped because a dependency failed", summary.skipped());
+    print("Failed", summary.failed());
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn module(dir: &Path) -> build::Module {
+        build::Module::new("m", dir.to_path_buf(), dir.join("build"), HashSet::new(), build::ConfigValue::new())
+    }
+
+    #[test]
+    fn project_search() {
+        let dir = env::temp_dir().join(format!("mib-main-test-{}", process::id()));
+        let src = dir.join("lib").join("net").join("src");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(dir.join("build.yml"), "module: []").unwrap();
+        let dir = fs::canonicalize(&dir).unwrap();
+        let src = fs::canonicalize(&src).unwrap();
+
+        // The build file is searched for from a subdirectory up to the project root.
+        assert_eq!(get_default_build_file(&Some(src.clone())), Some(dir.join("build.yml")));
+
+        let mut context = build::Context::new();
+        context.add_module("lib", module(&dir.join("lib")));
+        context.add_module("net", module(&dir.join("lib").join("net")));
+        assert_eq!(get_enclosing_module(&context, &src), Some("net".to_string()));
+        assert_eq!(get_enclosing_module(&context, &dir.join("lib")), Some("lib".to_string()));
+        assert_eq!(get_enclosing_module(&context, &dir), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn scoped_targets() {
+        let net = Some("net".to_string());
+
+        // Targets without a module refer to the enclosing module first and to the project otherwise.
+        assert_eq!(scope_target("build", &net), vec!("net:build", "build"));
+        assert_eq!(scope_target("^build", &net), vec!("^net:build", "^build"));
+        assert_eq!(scope_target("build", &None), vec!("build"));
+
+        // Qualified targets are left as they are.
+        assert_eq!(scope_target("lib:build", &net), vec!("lib:build"));
+        assert_eq!(scope_target("*:clean", &net), vec!("*:clean"));
+        assert_eq!(scope_target("//build", &net), vec!("build"));
+    }
+}
\ No newline at end of file
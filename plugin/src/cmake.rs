@@ -0,0 +1,452 @@
+use ::Plugin;
+use build::BuildError;
+use build::Context;
+use build::Fingerprint;
+use build::Module;
+use build::Task;
+use common;
+use common::CleanTask;
+use failure::Error;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use template;
+use template::Templates;
+
+pub const CMAKE_MODULE_TYPE: &'static str = "cmake";
+const CMAKE_CLEAN_TASK: &'static str = "clean";
+const CMAKE_BUILD_TASK: &'static str = "build";
+// A module with this file in its directory is built with it instead of the default one.
+const CMAKE_LISTS_FILE: &'static str = "CMakeLists.txt";
+
+pub const CMAKE_ARTIFACT_TYPE: &'static str = "cmake.artifact_type";
+pub const CMAKE_ARTIFACT_NAME: &'static str = "cmake.artifact_name";
+pub const CMAKE_BUILD_TYPE_PROPERTY: &'static str = "cmake.build_type";
+pub const CMAKE_GENERATOR_PROPERTY: &'static str = "cmake.generator";
+
+// Generated from the module and its dependencies for the template of the default CMakeLists.txt.
+const CMAKE_NAME_PROPERTY: &'static str = "cmake.name";
+const CMAKE_MODULE_PATH_PROPERTY: &'static str = "cmake.module_path";
+const CMAKE_PACKAGES_PROPERTY: &'static str = "cmake.packages";
+
+const CMAKE_LISTS_TEMPLATE: &'static str = "cmake/CMakeLists.txt";
+
+const CMAKE_ARTIFACT_TYPE_LIB: &'static str = "lib";
+const CMAKE_ARTIFACT_TYPE_BIN: &'static str = "bin";
+
+///
+/// Builds modules of type "cmake" with CMake alone, without Conan. Libraries are found by the modules depending on
+/// them with find_package() through the package config written into their build directory.
+///
+pub struct CMakePlugin {
+    templates: Templates
+}
+
+///
+/// A library module that other modules find with find_package().
+///
+#[derive(Debug, Clone)]
+struct Package {
+    // The package and library name. The imported target is "mib::<name>".
+    name: String,
+    build_dir: PathBuf
+}
+
+struct BuildTask {
+    module_path: PathBuf, // Directory of the module.
+    build_dir: PathBuf,
+    // The build directories of every profile, which are not part of the fingerprint.
+    build_dirs: Vec<PathBuf>,
+    // Whether the module has its own CMakeLists.txt.
+    own_lists: bool,
+    // Every package the module depends on, directly or not, so that find_package() can find them all.
+    packages: Vec<Package>,
+    definitions: Vec<(String, String)>,
+    build_type: Option<String>,
+    generator: Option<String>,
+    templates: Templates,
+    data: Value
+}
+
+impl CMakePlugin {
+    pub fn new(templates: &Templates) -> CMakePlugin {
+        CMakePlugin {
+            templates: templates.clone()
+        }
+    }
+
+    fn can_handle(&self, module: &Module) -> bool { module.types().contains(&CMAKE_MODULE_TYPE.to_string()) }
+}
+
+impl Plugin for CMakePlugin {
+
+    fn name(&self) -> String {
+        CMAKE_MODULE_TYPE.to_string()
+    }
+
+    fn get_documentation(&self) -> HashMap<String, String> {
+        let mut doc = HashMap::new();
+
+        doc.insert(
+            CMAKE_ARTIFACT_TYPE, indoc!(
+            "(Required unless the module has its own CMakeLists.txt) The type of artifact a module of type \"cmake\" \
+            produces. Possible values are \"lib\" for a library and \"bin\" for a binary.")
+            );
+        doc.insert(
+            CMAKE_ARTIFACT_NAME, indoc!(
+            "(Optional) The name of the library or executable a module of type \"cmake\" produces. The default value \
+            is the module name. Modules depending on a library find it with find_package(<name>) and link to the \
+            imported target mib::<name>. A module with its own CMakeLists.txt has to write <name>Config.cmake into \
+            its build directory itself, for example with export(TARGETS).")
+            );
+        doc.insert(
+            CMAKE_BUILD_TYPE_PROPERTY,
+            "(Optional) The CMAKE_BUILD_TYPE of a module of type \"cmake\", for example \"Debug\" or \"Release\"."
+            );
+        doc.insert(
+            CMAKE_GENERATOR_PROPERTY,
+            "(Optional) The CMake generator of a module of type \"cmake\", for example \"Ninja\"."
+            );
+
+        doc
+            .into_iter()
+            .map(|(k,v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn configure(&self, context: &mut Context) -> Result<(), Error> {
+        let build_task_name = |module_name: &String| {format!("{}:build", module_name)};
+        let clean_task_name = |module_name: &String| {format!("{}:clean", module_name)};
+
+        let mut handled_modules : HashSet<String> = HashSet::new();
+        let mut packages : HashMap<String, Package> = HashMap::new();
+        for (name, module) in context.modules_mut() {
+            let module = module.as_ref();
+            if !self.can_handle(module) {
+                trace!("CMake plugin skipping: {}", module.name());
+                continue;
+            }
+            handled_modules.insert(name.clone());
+            if get_artifact_type(module)? != Some(CMAKE_ARTIFACT_TYPE_BIN.to_string()) {
+                packages.insert(name.clone(), get_package(module)?);
+            }
+        }
+
+        // Module dependencies have to be read before the modules are borrowed below.
+        let mut module_deps : HashMap<String, Vec<String>> = HashMap::new();
+        for name in &handled_modules {
+            let mut deps : Vec<String> = context.get_module_deps(name)?.iter().filter(|d| handled_modules.contains(*d)).cloned().collect();
+            deps.sort();
+            module_deps.insert(name.clone(), deps);
+        }
+
+        let mut build_tasks : HashMap<String, Box<Task>> = HashMap::new();
+        let mut clean_tasks : HashMap<String, Box<Task>> = HashMap::new();
+        for (name, module) in context.modules_mut() {
+            let module = module.as_ref();
+            if !handled_modules.contains(name) {
+                continue;
+            }
+            trace!("CMake plugin configuring: {}", module.name());
+
+            let direct : Vec<Package> = module_deps[name].iter().filter_map(|d| packages.get(d)).cloned().collect();
+            let all = transitive_packages(name, &module_deps, &packages);
+
+            build_tasks.insert(build_task_name(name), Box::new(BuildTask::new(module, &direct, all, &self.templates)?));
+            clean_tasks.insert(clean_task_name(name), Box::new(CleanTask::new(module)?));
+        }
+
+        for (name, task) in build_tasks {
+            context.add_task(&name, task);
+
+            debug!("Task {} depends on {}.", CMAKE_BUILD_TASK, name);
+            context.task_mut(CMAKE_BUILD_TASK)?.depends_on(&name)?;
+        }
+
+        for (name, task) in clean_tasks {
+            context.add_task(&name, task);
+
+            debug!("Task {} depends on {}.", CMAKE_CLEAN_TASK, name);
+            context.task_mut(CMAKE_CLEAN_TASK)?.depends_on(&name)?;
+        }
+
+        // Set dependency relationships.
+        for (module, deps) in &module_deps {
+            for dep in deps {
+                let build_task = build_task_name(module);
+                let dep_task = build_task_name(dep);
+
+                debug!("Task {} depends on {}.", build_task, dep_task);
+                context.task_mut(&build_task)?.depends_on(&dep_task)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+///
+/// Every package a module depends on, directly or through other modules, ordered by name.
+///
+fn transitive_packages(name: &str, module_deps: &HashMap<String, Vec<String>>, packages: &HashMap<String, Package>) -> Vec<Package> {
+    let mut all = vec!();
+    let mut pending = module_deps[name].clone();
+    let mut seen = HashSet::new();
+    while let Some(dep) = pending.pop() {
+        if seen.insert(dep.clone()) {
+            if let Some(package) = packages.get(&dep) {
+                all.push(package.clone());
+            }
+            pending.extend(module_deps[&dep].iter().cloned());
+        }
+    }
+    all.sort_by(|a, b| a.name.cmp(&b.name));
+    all
+}
+
+///
+/// The artifact type of a module. Modules with their own CMakeLists.txt do not need one.
+///
+fn get_artifact_type(m: &Module) -> Result<Option<String>, Error> {
+    match m.config().get(CMAKE_ARTIFACT_TYPE) {
+        Some(t) if t == CMAKE_ARTIFACT_TYPE_LIB || t == CMAKE_ARTIFACT_TYPE_BIN => Ok(Some(t.clone())),
+        Some(t) => Err(common::config_error(m, CMAKE_ARTIFACT_TYPE, format!(
+            "{} is \"{}\" but must be \"{}\" or \"{}\".", CMAKE_ARTIFACT_TYPE, t, CMAKE_ARTIFACT_TYPE_LIB, CMAKE_ARTIFACT_TYPE_BIN
+        )))?,
+        None if m.module_dir().join(CMAKE_LISTS_FILE).is_file() => Ok(None),
+        None => Err(common::config_error(m, CMAKE_ARTIFACT_TYPE, format!("{} must be defined.", CMAKE_ARTIFACT_TYPE)))?
+    }
+}
+
+fn get_package(m: &Module) -> Result<Package, Error> {
+    Ok(Package {
+        name: m.config().get(CMAKE_ARTIFACT_NAME).unwrap_or(m.name()).clone(),
+        build_dir: common::get_build_dir(m, &common::get_module_path(m)?)
+    })
+}
+
+///
+/// A path as written in CMake scripts, which treat backslashes as escapes.
+///
+fn cmake_path(path: &PathBuf) -> String {
+    path.display().to_string().replace("\\", "/")
+}
+
+impl BuildTask {
+
+    fn new(m: &Module, direct: &[Package], packages: Vec<Package>, templates: &Templates) -> Result<BuildTask, Error> {
+        let module_path = common::get_module_path(m)?;
+        let build_dir = common::get_build_dir(m, &module_path);
+        let own_lists = module_path.join(CMAKE_LISTS_FILE).is_file();
+
+        let mut data = template::to_data(m.config_tree());
+        let artifact_type = get_artifact_type(m)?.unwrap_or(CMAKE_ARTIFACT_TYPE_LIB.to_string());
+        let direct : Vec<String> = direct.iter().map(|p| p.name.clone()).collect();
+        let flags = common::get_cmake_flags(m)?.into_iter().map(|(k, v)| (k, Value::from(v))).collect();
+
+        template::set(&mut data, CMAKE_NAME_PROPERTY, Value::from(m.name().clone()));
+        template::set(&mut data, CMAKE_MODULE_PATH_PROPERTY, Value::from(cmake_path(&module_path)));
+        template::set(&mut data, CMAKE_ARTIFACT_TYPE, Value::from(artifact_type));
+        template::set(&mut data, CMAKE_ARTIFACT_NAME, Value::from(get_package(m)?.name));
+        template::set(&mut data, CMAKE_PACKAGES_PROPERTY, Value::from(direct));
+        template::set(&mut data, common::CMAKE_FLAGS_PROPERTY, Value::Object(flags));
+
+        Ok(
+            BuildTask {
+                build_dirs: common::get_build_dirs(m, &module_path),
+                module_path: module_path,
+                build_dir: build_dir,
+                own_lists: own_lists,
+                packages: packages,
+                definitions: common::get_cmake_definitions(m)?,
+                build_type: m.config().get(CMAKE_BUILD_TYPE_PROPERTY).cloned(),
+                generator: m.config().get(CMAKE_GENERATOR_PROPERTY).cloned(),
+                templates: templates.clone(),
+                data: data
+            }
+        )
+    }
+
+    ///
+    /// Renders the template of the CMakeLists.txt unless the module has its own.
+    ///
+    fn render_lists(&self) -> Result<Option<String>, Error> {
+        if self.own_lists {
+            return Ok(None);
+        }
+        Ok(Some(self.templates.render(&self.module_path, CMAKE_LISTS_TEMPLATE, &self.data)?))
+    }
+
+    ///
+    /// The arguments that generate the build system in the build directory.
+    ///
+    fn configure_args(&self) -> Vec<String> {
+        let source_dir = if self.own_lists { &self.module_path } else { &self.build_dir };
+        let mut args = vec!(
+            "-S".to_string(), source_dir.display().to_string(),
+            "-B".to_string(), self.build_dir.display().to_string()
+        );
+        if let Some(generator) = &self.generator {
+            args.push("-G".to_string());
+            args.push(generator.clone());
+        }
+        if let Some(build_type) = &self.build_type {
+            args.push(format!("-DCMAKE_BUILD_TYPE={}", build_type));
+        }
+        for package in &self.packages {
+            args.push(format!("-D{}_DIR={}", package.name, cmake_path(&package.build_dir)));
+        }
+        for (k, v) in &self.definitions {
+            args.push(format!("-D{}={}", k, v));
+        }
+        args
+    }
+
+    fn build_args(&self) -> Vec<String> {
+        let mut args = vec!("--build".to_string(), self.build_dir.display().to_string());
+        // Multi-config generators such as Visual Studio choose the build type when building.
+        if let Some(build_type) = &self.build_type {
+            args.push("--config".to_string());
+            args.push(build_type.clone());
+        }
+        args
+    }
+}
+
+impl Task for BuildTask {
+
+    fn needs_execution(&self) -> bool {
+        // Only used when fingerprints are unavailable.
+        true
+    }
+
+    fn fingerprint(&self) -> Result<Option<String>, Error> {
+        let mut fingerprint = Fingerprint::new();
+
+        common::add_build_dir_state(&mut fingerprint, &self.build_dir);
+
+        for arg in self.configure_args().iter().chain(self.build_args().iter()) {
+            fingerprint.add(arg);
+        }
+        if let Some(lists) = self.render_lists()? {
+            fingerprint.add(lists);
+        }
+        common::add_module_files(&mut fingerprint, &self.module_path, &self.build_dirs)?;
+
+        Ok(Some(fingerprint.finish()))
+    }
+
+    fn execute(&self) -> Result<(), Error> {
+        // The build directory is kept between builds so that CMake only rebuilds what changed.
+        if let Err(e) = fs::create_dir_all(&self.build_dir) {
+            Err(BuildError::IOError(format!("Error creating build directory at {:?}: {}", self.build_dir, e)))?
+        }
+        if let Some(lists) = self.render_lists()? {
+            let path = self.build_dir.join(CMAKE_LISTS_FILE);
+            debug!("Writing: {:?}", path);
+            if let Err(e) = fs::write(&path, lists) { Err(BuildError::IOError(format!("Unable to write {:?}: {}", path, e)))? }
+        }
+
+        cmake(&self.module_path, &self.configure_args())?;
+        cmake(&self.module_path, &self.build_args())
+    }
+}
+
+fn cmake(wd: &PathBuf, args: &[String]) -> Result<(), Error> {
+    let mut cmd = Command::new("cmake");
+    cmd.args(args).current_dir(wd);
+
+    debug!("Command: {:?}", cmd);
+
+    let status = match cmd.status() {
+        Ok(s) => s,
+        Err(e) => Err(BuildError::ExecutionError(format!("Unable to run cmake: {}", e)))?
+    };
+    debug!("Status: {:?}", status);
+    if !status.success() {
+        Err(BuildError::ExecutionError(format!("Process failed: {}", status)))?
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use build::ConfigValue;
+    use std::env;
+    use std::process;
+
+    fn module(dir: &PathBuf, artifact_type: Option<&str>) -> Module {
+        let config = artifact_type
+            .map(|t| vec!((CMAKE_ARTIFACT_TYPE.to_string(), ConfigValue::String(t.to_string()))))
+            .unwrap_or(vec!());
+        Module::new("hello", dir.clone(), dir.join("build"), HashSet::new(), ConfigValue::Map(config.into_iter().collect()))
+    }
+
+    #[test]
+    fn artifact_types() {
+        let dir = env::temp_dir().join(format!("mib-cmake-test-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        assert_eq!(get_artifact_type(&module(&dir, Some("lib"))).unwrap(), Some("lib".to_string()));
+        assert_eq!(get_artifact_type(&module(&dir, Some("bin"))).unwrap(), Some("bin".to_string()));
+        assert!(get_artifact_type(&module(&dir, Some("shared"))).is_err());
+        assert!(get_artifact_type(&module(&dir, None)).is_err());
+
+        // A module with its own CMakeLists.txt does not need an artifact type but may still declare one.
+        fs::write(dir.join(CMAKE_LISTS_FILE), "project(hello)").unwrap();
+        assert_eq!(get_artifact_type(&module(&dir, None)).unwrap(), None);
+        assert_eq!(get_artifact_type(&module(&dir, Some("bin"))).unwrap(), Some("bin".to_string()));
+        assert!(get_artifact_type(&module(&dir, Some("shared"))).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn packages() {
+        let deps = |d: &[&str]| d.iter().map(|d| d.to_string()).collect::<Vec<String>>();
+        let package = |name: &str| Package { name: name.to_string(), build_dir: PathBuf::from(format!("/p/{}/build", name)) };
+
+        // app -> net -> {base, tool}, where tool is a binary without a package, and app -> base again.
+        let mut module_deps = HashMap::new();
+        module_deps.insert("app".to_string(), deps(&["net", "base"]));
+        module_deps.insert("net".to_string(), deps(&["base", "tool"]));
+        module_deps.insert("tool".to_string(), deps(&["base"]));
+        module_deps.insert("base".to_string(), deps(&[]));
+        let mut packages = HashMap::new();
+        packages.insert("net".to_string(), package("net"));
+        packages.insert("base".to_string(), package("base_lib"));
+
+        let names = |name: &str| transitive_packages(name, &module_deps, &packages).into_iter().map(|p| p.name).collect::<Vec<String>>();
+        assert_eq!(names("app"), vec!("base_lib", "net"));
+        assert_eq!(names("net"), vec!("base_lib"));
+        assert_eq!(names("tool"), vec!("base_lib"));
+        assert!(names("base").is_empty());
+    }
+
+    #[test]
+    fn arguments() {
+        let task = BuildTask {
+            module_path: PathBuf::from("/p/app"),
+            build_dir: PathBuf::from("/p/app/build"),
+            build_dirs: vec!(PathBuf::from("/p/app/build")),
+            own_lists: false,
+            packages: vec!(Package { name: "hello".to_string(), build_dir: PathBuf::from("/p/hello/build") }),
+            definitions: vec!(("USE_SSL".to_string(), "ON".to_string())),
+            build_type: Some("Release".to_string()),
+            generator: None,
+            templates: Templates::new(vec!()),
+            data: Value::Null
+        };
+
+        assert_eq!(
+            task.configure_args(),
+            vec!("-S", "/p/app/build", "-B", "/p/app/build", "-DCMAKE_BUILD_TYPE=Release", "-Dhello_DIR=/p/hello/build", "-DUSE_SSL=ON")
+        );
+        assert_eq!(task.build_args(), vec!("--build", "/p/app/build", "--config", "Release"));
+    }
+}
\ No newline at end of file
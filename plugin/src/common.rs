@@ -0,0 +1,221 @@
+use build::BuildError;
+use build::ConfigValue;
+use build::Fingerprint;
+use build::Module;
+use build::Task;
+use dunce;
+use failure::Error;
+use std::fs;
+use std::path::PathBuf;
+use walkdir::WalkDir;
+
+const CLEAN_TASK: &'static str = "clean";
+
+pub const CMAKE_DEFINITIONS_PROPERTY: &'static str = "cmake.definitions";
+pub const CMAKE_FLAGS_PROPERTY: &'static str = "cmake.flags";
+
+///
+/// Removes the build directory of a module.
+///
+pub struct CleanTask {
+    name: String,
+    module_path: PathBuf, // Directory of the module.
+    build_dir: PathBuf
+}
+
+///
+/// A configuration error pointing at the key if the module defines it or at the module otherwise.
+///
+pub fn config_error(m: &Module, key: &str, message: String) -> BuildError {
+    match m.config_location(key).or(m.location()) {
+        Some(location) => BuildError::ConfigError(location.describe(&format!("Module {}: {}", m.name(), message))),
+        None => BuildError::ConfigError(format!("Module {}: {}", m.name(), message))
+    }
+}
+
+///
+/// Get a map from the module config. Returns an empty map if it is not defined.
+///
+pub fn get_config_map<'a>(m: &'a Module, key: &str) -> Result<Vec<(&'a String, &'a ConfigValue)>, Error> {
+    match m.config_value(key) {
+        Some(ConfigValue::Map(map)) => Ok(map.iter().collect()),
+        Some(v) => Err(config_error(m, key, format!("{} must be a map but was {}.", key, v)))?,
+        None => Ok(vec!())
+    }
+}
+
+///
+/// The canonical path of the module directory.
+///
+pub fn get_module_path(m: &Module) -> Result<PathBuf, Error> {
+    match dunce::canonicalize(&m.module_dir()) {
+        Ok(a) => Ok(a),
+        Err(e) => Err(BuildError::IOError(format!("Error canonicalizing module path {}: {}", m.module_dir().display(), e)))?
+    }
+}
+
+///
+/// The build directory of the module below the canonical module path.
+///
+pub fn get_build_dir(m: &Module, module_path: &PathBuf) -> PathBuf {
+    match m.build_dir().strip_prefix(m.module_dir()) {
+        Ok(relative) => module_path.join(relative),
+        Err(_) => m.build_dir().clone()
+    }
+}
+
+///
+/// Gets the build directories of every profile of a module, relative to the canonical module path.
+///
+pub fn get_build_dirs(m: &Module, module_path: &PathBuf) -> Vec<PathBuf> {
+    m.build_dirs().iter().map(|d| match d.strip_prefix(m.module_dir()) {
+        Ok(relative) => module_path.join(relative),
+        Err(_) => d.clone()
+    }).collect()
+}
+
+///
+/// Adds whether the build directory exists to a fingerprint so that a cleaned module builds again. Since the executor
+/// records fingerprints after executing, a module that was just built is up to date on the next run.
+///
+pub fn add_build_dir_state(fingerprint: &mut Fingerprint, build_dir: &PathBuf) {
+    fingerprint.add(if build_dir.is_dir() { "built" } else { "clean" });
+}
+
+///
+/// Adds the names and contents of the files of a module to a fingerprint, leaving out hidden files and the excluded
+/// paths, like the build directories of the module.
+///
+pub fn add_module_files(fingerprint: &mut Fingerprint, module_path: &PathBuf, excluded: &[PathBuf]) -> Result<(), Error> {
+    for entry in WalkDir::new(module_path).sort_by(|a, b| a.file_name().cmp(b.file_name())).into_iter().filter_entry(
+        |e| {
+            !(e.depth() > 0 && e.file_name().to_string_lossy().starts_with(".")) && // Ignore hidden files like the mib state directory.
+            !excluded.iter().any(|p| p == e.path())
+        }
+    ) {
+        let entry = match entry {
+            Ok(e) => e,
+            Err(e) => Err(BuildError::IOError(format!("Error accessing path: {}", e)))?
+        };
+        if !entry.file_type().is_file() {
+            continue
+        }
+
+        trace!("Fingerprinting: {}", entry.path().display());
+
+        let relative = entry.path().strip_prefix(module_path).unwrap_or(entry.path());
+        fingerprint.add(relative.to_string_lossy().as_bytes());
+        fingerprint.add_file(entry.path())?;
+    }
+    Ok(())
+}
+
+///
+/// The CMake definitions of the module ordered by name. Booleans become ON and OFF and lists are joined with ";".
+///
+pub fn get_cmake_definitions(m: &Module) -> Result<Vec<(String, String)>, Error> {
+    let mut definitions = vec!();
+    for (k, v) in get_config_map(m, CMAKE_DEFINITIONS_PROPERTY)? {
+        let value = match v {
+            ConfigValue::Bool(true) => "ON".to_string(),
+            ConfigValue::Bool(false) => "OFF".to_string(),
+            ConfigValue::List(l) => l.iter().map(|v| v.to_string()).collect::<Vec<String>>().join(";"),
+            v => v.to_string()
+        };
+        definitions.push((k.clone(), value));
+    }
+    Ok(definitions)
+}
+
+///
+/// The compile flags of the module by CMake compiler id, for example "GNU" for gcc.
+///
+pub fn get_cmake_flags(m: &Module) -> Result<Vec<(String, String)>, Error> {
+    let mut compiler_flags = vec!();
+    for (compiler, flags) in get_config_map(m, CMAKE_FLAGS_PROPERTY)? {
+        let compiler_id = match compiler.as_str() {
+            "gcc" => "GNU",
+            "clang" => "Clang",
+            "appleclang" => "AppleClang",
+            "msvc" => "MSVC",
+            other => other
+        };
+        let flags : Vec<String> = match flags {
+            ConfigValue::List(l) => l.iter().map(|f| f.to_string()).collect(),
+            f => f.to_string().split_whitespace().map(|f| f.to_string()).collect()
+        };
+        compiler_flags.push((compiler_id.to_string(), flags.join(" ")));
+    }
+    Ok(compiler_flags)
+}
+
+impl CleanTask {
+    pub fn new(m: &Module) -> Result<CleanTask, Error> {
+        let module_path = get_module_path(m)?;
+        let build_dir = get_build_dir(m, &module_path);
+
+        Ok(
+            CleanTask {
+                name: String::from(CLEAN_TASK),
+                module_path: module_path,
+                build_dir: build_dir
+            }
+        )
+    }
+}
+
+impl Task for CleanTask {
+
+    fn needs_execution(&self) -> bool {
+        true
+    }
+
+    fn execute(&self) -> Result<(), Error> {
+        if !self.build_dir.exists() {
+            return Ok(());
+        }
+
+        match fs::remove_dir_all(&self.build_dir) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(BuildError::IOError(format!("Unable to remove directory {:?}: {}", &self.build_dir, e)))?
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::collections::HashSet;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn module_files() {
+        let dir = env::temp_dir().join(format!("mib-common-test-{}", process::id()));
+        for d in &["src", "build-tools", "build", "build-release", ".mib"] {
+            fs::create_dir_all(dir.join(d)).unwrap();
+            fs::write(dir.join(d).join("a.txt"), "a").unwrap();
+        }
+        let mut m = Module::new("hello", dir.clone(), dir.join("build"), HashSet::new(), ConfigValue::Map(BTreeMap::new()));
+        m.set_build_dirs(vec!(dir.join("build"), dir.join("build-release")));
+        let fingerprint = || {
+            let mut fingerprint = Fingerprint::new();
+            add_module_files(&mut fingerprint, &dir, &get_build_dirs(&m, &dir)).unwrap();
+            fingerprint.finish()
+        };
+        let before = fingerprint();
+
+        // Only the build directories of the module and hidden files are left out, not sources named like them.
+        for d in &["build", "build-release", ".mib"] {
+            fs::write(dir.join(d).join("a.txt"), "b").unwrap();
+        }
+        assert_eq!(fingerprint(), before);
+        fs::write(dir.join("build-tools").join("a.txt"), "b").unwrap();
+        assert_ne!(fingerprint(), before);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
\ No newline at end of file
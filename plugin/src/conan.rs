@@ -1,25 +1,51 @@
 use ::Plugin;
 use build::BuildError;
+use build::ConfigValue;
 use build::Context;
+use build::Fingerprint;
 use build::Module;
 use build::Task;
-use chrono;
-use chrono::DateTime;
+use common::add_build_dir_state;
+use common::add_module_files;
+use common::config_error;
+use common::get_build_dir;
+use common::get_build_dirs;
+use common::get_cmake_definitions;
+use common::get_cmake_flags;
+use common::get_config_map;
+use common::get_module_path;
+use common::CleanTask;
+use common::CMAKE_DEFINITIONS_PROPERTY;
+use common::CMAKE_FLAGS_PROPERTY;
 use dunce;
 use failure::Error;
 use path_util;
+use serde_json;
+use serde_json::Value;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::env::consts;
 use std::fs;
 use std::path::PathBuf;
 use std::process::Command;
-use timeago;
+use template;
+use template::Templates;
 
 pub const CONAN_MODULE_TYPE: &'static str = "conan";
 const CONAN_CLEAN_TASK: &'static str = "clean";
 const CONAN_BUILD_TASK: &'static str = "build";
-const CONAN_BUILD_DIRECTORY: &'static str = "build";
-const CONAN_TEST_DIRECTORY: &'static str = "build";
+const CONAN_TEST_TASK: &'static str = "test";
+// Merges the compile databases of the modules built so far without building anything.
+const CONAN_COMPDB_TASK: &'static str = "compdb";
+// Merges the compile databases as part of the build, once every module is built.
+const CONAN_BUILD_COMPDB_TASK: &'static str = "build-compdb";
+// The compile database of the whole project, relative to the project root.
+const COMPILE_COMMANDS_FILE: &'static str = "compile_commands.json";
+// The folder of a module containing its tests, which are built in the folder of the same name in its build directory.
+const CONAN_TEST_DIRECTORY: &'static str = "test";
+// The JUnit XML results of the tests below the test build directory.
+const CONAN_TEST_REPORT: &'static str = "junit.xml";
+const CONAN_DEFAULT_TEST_REQUIRES: &'static str = "Catch2/2.5.2@catchorg/stable";
 
 pub const CONAN_NAME_PROPERTY: &'static str = "conan.name";
 pub const CONAN_VERSION_PROPERTY: &'static str = "conan.version";
@@ -31,25 +57,43 @@ pub const CONAN_MODULE_PATH_PROPERTY: &'static str = "conan.module_path";
 pub const CONAN_CONANFILE_PATH_PROPERTY: &'static str = "conan.conanfile";
 pub const CONAN_ARTIFACT_TYPE: &'static str = "conan.artifact_type";
 pub const CONAN_ARTIFACT_NAME: &'static str = "conan.artifact_name";
+pub const CONAN_OPTIONS_PROPERTY: &'static str = "conan.options";
+pub const CONAN_BUILD_TYPE_PROPERTY: &'static str = "conan.build_type";
+pub const CONAN_SETTINGS_PROPERTY: &'static str = "conan.settings";
+pub const CONAN_TEST_REQUIRES_PROPERTY: &'static str = "conan.test_requires";
+
+// Generated from the properties above for the templates of the build scripts.
+const CONAN_DEFAULT_OPTIONS_PROPERTY: &'static str = "conan.default_options";
+
+const CONAN_CONANFILE_TEMPLATE: &'static str = "conan/conanfile.py";
+const CONAN_CMAKELISTS_TEMPLATE: &'static str = "conan/CMakeLists.txt";
+const CONAN_TEST_CONANFILE_TEMPLATE: &'static str = "conan/test/conanfile.py";
+const CONAN_TEST_CMAKELISTS_TEMPLATE: &'static str = "conan/test/CMakeLists.txt";
 
 const CONAN_ARTIFACT_TYPE_LIB: &'static str = "lib";
 const CONAN_ARTIFACT_TYPE_BIN: &'static str = "bin";
+// A header-only library, which is packaged without compiling anything.
+const CONAN_ARTIFACT_TYPE_HEADER: &'static str = "header";
 
-pub struct ConanPlugin {}
+pub struct ConanPlugin {
+    templates: Templates
+}
 
 #[derive(Debug)]
 struct CreateConfig {
         wd: PathBuf,
         conanfile: PathBuf,
         user: String,
-        channel: String
+        channel: String,
+        settings: Vec<(String, String)>
 }
 
 #[derive(Debug)]
 struct InstallConfig {
         wd: PathBuf,
         conanfile: PathBuf,
-        install_folder: PathBuf
+        install_folder: PathBuf,
+        settings: Vec<(String, String)>
 }
 
 #[derive(Debug)]
@@ -58,7 +102,9 @@ struct BuildConfig {
         conanfile: PathBuf,
         source_folder: PathBuf,
         install_folder: PathBuf,
-        build_folder: PathBuf
+        build_folder: PathBuf,
+        // Only generate the build system, for example to get the compile database of a library.
+        configure_only: bool
 }
 
 #[derive(Debug)]
@@ -68,40 +114,63 @@ enum ConanConfig {
     Build(BuildConfig)
 }
 
-struct CleanTask {
-    name: String,
+///
+/// Merges the compile databases of the modules into one at the project root.
+///
+struct CompdbTask {
+    output: PathBuf,
+    // The build directory of each module with the directory its sources are copied from.
+    modules: Vec<(PathBuf, PathBuf)>
+}
+
+struct TestTask {
     module_path: PathBuf, // Directory of the module.
-    build_dir: PathBuf
+    // Paths of the module that are not part of the fingerprint.
+    excluded: Vec<PathBuf>,
+    test_dir: PathBuf,
+    binary: PathBuf,
+    report: PathBuf,
+    settings: Vec<(String, String)>,
+    templates: Templates,
+    data: Value
 }
 
 struct BuildTask {
     name: String,
     module_path: PathBuf, // Directory of the module.
     build_dir: PathBuf,
+    // Paths of the module that are not part of the fingerprint.
+    excluded: Vec<PathBuf>,
     conanfile: PathBuf,
     artifact_type: String,
     user: String,
     channel: String,
-    config: HashMap<String, String>
+    // Conan settings such as build_type, passed to "conan install" and "conan create".
+    settings: Vec<(String, String)>,
+    config: HashMap<String, String>,
+    templates: Templates,
+    data: Value
 }
 
 impl ConanPlugin {
-    pub fn new() -> ConanPlugin {
-        ConanPlugin {}
+    pub fn new(templates: &Templates) -> ConanPlugin {
+        ConanPlugin {
+            templates: templates.clone()
+        }
     }
 
     fn can_handle(&self, module: &Module) -> bool { module.types().contains(&CONAN_MODULE_TYPE.to_string()) }
 
-    fn validate_config(&self, config: &HashMap<String, String>) -> Result<(), Error> {
+    fn validate_config(&self, module: &Module) -> Result<(), Error> {
+        let config = module.config();
 
         for (k,v) in config {
-            println!("{}={}", k, v);
+            trace!("{}={}", k, v);
         }
-        println!();
 
         let is_defined = |var| -> Result<(), Error> {
             if !config.contains_key(var) {
-                Err(BuildError::ConfigError(format!("{} must be defined.", var)))?
+                Err(config_error(module, var, format!("{} must be defined.", var)))?
             } else {
                 Ok(())
             }
@@ -112,7 +181,7 @@ impl ConanPlugin {
             if config.contains_key(var) && config.get(var) == Some(&val) {
                 Ok(())
             } else {
-                Err(BuildError::ConfigError(format!("{} is not defined as {}.", var, val)))?
+                Err(config_error(module, var, format!("{} is not defined as {}.", var, val)))?
             }
         };
 
@@ -121,7 +190,7 @@ impl ConanPlugin {
             if config.contains_key(var) && config.get(var) == Some(&val) {
                 Ok(())
             } else {
-                Err(BuildError::ConfigError(msg))?
+                Err(config_error(module, var, msg))?
             }
         };
 
@@ -130,7 +199,7 @@ impl ConanPlugin {
             .iter()
             .map(|x| is_value(var, x))
             .fold(
-                Err(Error::from(BuildError::ConfigError(format!("{} is not defined as any of {:?}", var, val)))),
+                Err(Error::from(config_error(module, var, format!("{} is not defined as any of {:?}", var, val)))),
                 |acc, val| acc.or(val)
             )
         };
@@ -138,9 +207,10 @@ impl ConanPlugin {
         return
             is_defined(CONAN_CONANFILE_PATH_PROPERTY).or(
                 is_defined(CONAN_VERSION_PROPERTY)
-                .and(is_one_of(CONAN_ARTIFACT_TYPE, &[CONAN_ARTIFACT_TYPE_BIN, CONAN_ARTIFACT_TYPE_LIB]))
+                .and(is_one_of(CONAN_ARTIFACT_TYPE, &[CONAN_ARTIFACT_TYPE_BIN, CONAN_ARTIFACT_TYPE_LIB, CONAN_ARTIFACT_TYPE_HEADER]))
                 .and(
                     is_value(CONAN_ARTIFACT_TYPE, CONAN_ARTIFACT_TYPE_LIB)
+                    .or(is_value(CONAN_ARTIFACT_TYPE, CONAN_ARTIFACT_TYPE_HEADER))
                     .and(is_defined(CONAN_USER_PROPERTY))
                     .and(is_defined(CONAN_CHANNEL_PROPERTY))
                     .and(is_defined(CONAN_ARTIFACT_NAME))
@@ -149,7 +219,7 @@ impl ConanPlugin {
                             format!(
                                 "{} was defined as \"{}\" but all of {}, {}, and {} were not also defined.",
                                 CONAN_ARTIFACT_TYPE,
-                                CONAN_ARTIFACT_TYPE_LIB,
+                                config.get(CONAN_ARTIFACT_TYPE).map(|t| t.as_str()).unwrap_or(CONAN_ARTIFACT_TYPE_LIB),
                                 CONAN_USER_PROPERTY,
                                 CONAN_CHANNEL_PROPERTY,
                                 CONAN_ARTIFACT_NAME
@@ -163,20 +233,51 @@ impl ConanPlugin {
 
 impl Plugin for ConanPlugin {
 
+    fn name(&self) -> String {
+        CONAN_MODULE_TYPE.to_string()
+    }
+
     fn get_documentation(&self) -> HashMap<String, String> {
         let mut doc = HashMap::new();
 
         doc.insert(CONAN_NAME_PROPERTY, "(Optional) The name of the Conan project. The default value is the module name.");
         doc.insert(CONAN_VERSION_PROPERTY, "(Required) The version of the Conan project.");
-        doc.insert(CONAN_USER_PROPERTY, "(Required for artifacts of type \"lib\") The user of the Conan project.");
-        doc.insert(CONAN_CHANNEL_PROPERTY, "(Required for artifacts of type \"lib\") The channel of the Conan project.");
-        doc.insert(CONAN_ARTIFACT_TYPE, "(Required) The type of artifact this module produces. Possible values are \"lib\" for a library and \"bin\" for a binary.");
-        doc.insert(CONAN_ARTIFACT_NAME, "(Required for artifacts of type \"lib\") The name of the shared library/executable this module produces. This does not include the file extension or the \"lib\" prefix on Unix systems.");
+        doc.insert(CONAN_USER_PROPERTY, "(Required for artifacts of type \"lib\" or \"header\") The user of the Conan project.");
+        doc.insert(CONAN_CHANNEL_PROPERTY, "(Required for artifacts of type \"lib\" or \"header\") The channel of the Conan project.");
+        doc.insert(CONAN_ARTIFACT_TYPE, "(Required) The type of artifact this module produces. Possible values are \"lib\" for a library, \"header\" for a header-only library and \"bin\" for a binary.");
+        doc.insert(CONAN_ARTIFACT_NAME, "(Required for artifacts of type \"lib\" or \"header\") The name of the shared library/executable/interface target this module produces. This does not include the file extension or the \"lib\" prefix on Unix systems.");
         doc.insert(CONAN_DESCRIPTION_PROPERTY, "(Optional) The description of the Conan project.");
         doc.insert(
             CONAN_REQUIRES_PROPERTY, indoc!(
-            "(Optional) The dependencies of the Conan project as a list. \
-            If unspecified then there are no dependencies.")
+            "(Optional) Additional dependencies of the Conan project as a list. \
+            The libraries of conan modules listed in the module's deps are required automatically.")
+            );
+        doc.insert(
+            CONAN_OPTIONS_PROPERTY, indoc!(
+            "(Optional) A map of conan options, for example {shared: true} or {\"boost:shared\": false}. \
+            The default conanfile only declares the \"shared\" option itself.")
+            );
+        doc.insert(
+            CONAN_BUILD_TYPE_PROPERTY,
+            "(Optional) The conan build_type setting, for example \"Debug\" or \"Release\". Usually set by a profile."
+            );
+        doc.insert(
+            CONAN_SETTINGS_PROPERTY,
+            "(Optional) A map of additional conan settings, for example {compiler.libcxx: libstdc++11}."
+            );
+        doc.insert(
+            CONAN_TEST_REQUIRES_PROPERTY, indoc!(
+            "(Optional) The test framework required by the tests in the module's \"test\" folder. \
+            The default value is Catch2/2.5.2@catchorg/stable.")
+            );
+        doc.insert(
+            CMAKE_DEFINITIONS_PROPERTY,
+            "(Optional) A map of CMake variables set by the default CMakeLists.txt, for example {USE_SSL: true}."
+            );
+        doc.insert(
+            CMAKE_FLAGS_PROPERTY, indoc!(
+            "(Optional) A map of compile flags per compiler, for example {gcc: -Wall -Wextra, msvc: /W4}. \
+            The compilers are gcc, clang, appleclang and msvc. Flags are a string or a list.")
             );
         doc.insert(
             CONAN_MODULE_PATH_PROPERTY, indoc!(
@@ -207,19 +308,54 @@ impl Plugin for ConanPlugin {
     fn configure(&self, context: &mut Context) -> Result<(), Error> {
         let mut build_tasks : HashMap<String, Box<Task>> = HashMap::new();
         let mut clean_tasks : HashMap<String, Box<Task>> = HashMap::new();
+        let mut test_tasks : HashMap<String, Box<Task>> = HashMap::new();
         let mut handled_modules : HashSet<String> = HashSet::new();
+        let mut compdb_modules : Vec<(String, PathBuf, PathBuf)> = vec!();
 
         let build_task_name = |module_name: &String| {format!("{}:build", module_name)};
         let clean_task_name = |module_name: &String| {format!("{}:clean", module_name)};
+        let test_task_name = |module_name: &String| {format!("{}:test", module_name)};
+
+        // Module dependencies have to be read before the modules are borrowed below.
+        let module_names : Vec<String> = context.modules_mut().keys().cloned().collect();
+        let mut module_deps : HashMap<String, HashSet<String>> = HashMap::new();
+        for name in module_names {
+            let deps = context.get_module_deps(&name)?.clone();
+            module_deps.insert(name, deps);
+        }
+
+        // The conan references of all libraries so that they can be required by the modules depending on them.
+        let dependencies : HashSet<String> = module_deps.values().flat_map(|deps| deps.iter().cloned()).collect();
+        let mut references : HashMap<String, String> = HashMap::new();
+        for (name, module) in context.modules_mut() {
+            if self.can_handle(module) && dependencies.contains(name) {
+                if let Some(reference) = get_conan_reference(module)? {
+                    references.insert(name.clone(), reference);
+                }
+            }
+        }
 
         for (name, module) in context.modules_mut() {
             let module = module.as_ref();
 
-            if self.can_handle(module) && self.validate_config(module.config())? == () {
+            if self.can_handle(module) && self.validate_config(module)? == () {
                 trace!("Conan plugin configuring: {}", module.name());
 
-                build_tasks.insert(build_task_name(name), Box::new(BuildTask::new(module)?));
+                let mut requires : Vec<String> = module_deps[name]
+                    .iter()
+                    .filter_map(|dep| references.get(dep))
+                    .cloned()
+                    .collect();
+                requires.sort();
+
+                build_tasks.insert(build_task_name(name), Box::new(BuildTask::new(module, &requires, &self.templates)?));
                 clean_tasks.insert(clean_task_name(name), Box::new(CleanTask::new(module)?));
+                if module.module_dir().join(CONAN_TEST_DIRECTORY).is_dir() {
+                    test_tasks.insert(name.clone(), Box::new(TestTask::new(module, &requires, &self.templates)?));
+                }
+
+                let module_path = get_module_path(module)?;
+                compdb_modules.push((name.clone(), get_build_dir(module, &module_path), module_path));
 
                 handled_modules.insert(name.clone());
 
@@ -242,6 +378,30 @@ impl Plugin for ConanPlugin {
             context.task_mut(CONAN_CLEAN_TASK)?.depends_on(&name)?;
         }
 
+        // The same merge is available on its own and as part of the build, where it waits for every module.
+        if !compdb_modules.is_empty() {
+            compdb_modules.sort();
+            let modules : Vec<(PathBuf, PathBuf)> = compdb_modules.iter().map(|(_, build_dir, module_path)| (build_dir.clone(), module_path.clone())).collect();
+            context.add_task(CONAN_COMPDB_TASK, Box::new(CompdbTask::new(modules.clone())));
+            context.add_task(CONAN_BUILD_COMPDB_TASK, Box::new(CompdbTask::new(modules)));
+            for (name, _, _) in &compdb_modules {
+                context.task_mut(CONAN_BUILD_COMPDB_TASK)?.depends_on(&build_task_name(name))?;
+            }
+
+            debug!("Task {} depends on {}.", CONAN_BUILD_TASK, CONAN_BUILD_COMPDB_TASK);
+            context.task_mut(CONAN_BUILD_TASK)?.depends_on(CONAN_BUILD_COMPDB_TASK)?;
+        }
+
+        // Tests run once the module is built.
+        for (module, task) in test_tasks {
+            let name = test_task_name(&module);
+            context.add_task(&name, task);
+            context.task_mut(&name)?.depends_on(&build_task_name(&module))?;
+
+            debug!("Task {} depends on {}.", CONAN_TEST_TASK, name);
+            context.task_mut(CONAN_TEST_TASK)?.depends_on(&name)?;
+        }
+
         // Set dependency relationships.
         for module in &handled_modules {
             for dep in context.get_module_deps(module)?.clone() {
@@ -259,120 +419,191 @@ impl Plugin for ConanPlugin {
     }
 }
 
+///
+/// The data the templates of the build scripts are rendered with: the module config as written and the values
+/// generated from it.
+///
+fn get_template_data(m: &Module, module_path: &PathBuf, requires: &[String]) -> Result<Value, Error> {
+    let config = m.config();
+    let mut data = template::to_data(m.config_tree());
+
+    // Optional keys that the templates refer to. Paths use forward slashes as backslashes are escapes in scripts.
+    let module_path_string = module_path.display().to_string().replace("\\", "/");
+    let defaults = vec!(
+        (CONAN_NAME_PROPERTY, m.name().clone()),
+        (CONAN_DESCRIPTION_PROPERTY, String::new()),
+        (CONAN_ARTIFACT_NAME, m.name().clone()),
+        (CONAN_MODULE_PATH_PROPERTY, module_path_string)
+    );
+    for (key, value) in defaults {
+        if !config.contains_key(key) {
+            template::set(&mut data, key, Value::from(value));
+        }
+    }
+
+    let requires = merge_requires(config.get(CONAN_REQUIRES_PROPERTY), requires);
+    template::set(&mut data, CONAN_REQUIRES_PROPERTY, Value::from(split_requires(&requires)));
+    template::set(&mut data, CONAN_DEFAULT_OPTIONS_PROPERTY, Value::from(get_default_options(m)?));
+
+    // Values are quoted in the CMakeLists.txt.
+    let definitions = get_cmake_definitions(m)?.into_iter().map(|(k, v)| (k, Value::from(v.replace("\"", "\\\"")))).collect();
+    template::set(&mut data, CMAKE_DEFINITIONS_PROPERTY, Value::Object(definitions));
+    let flags = get_cmake_flags(m)?.into_iter().map(|(k, v)| (k, Value::from(v))).collect();
+    template::set(&mut data, CMAKE_FLAGS_PROPERTY, Value::Object(flags));
+
+    Ok(data)
+}
+
+fn split_requires(requires: &str) -> Vec<String> {
+    requires.split(',').filter(|r| !r.is_empty()).map(|r| r.to_string()).collect()
+}
+
 fn get_required_config(m: &Module, key: &str) -> Result<String, Error> {
     match m.config().get(&key.to_string()) {
         Some(value) => Ok(value.clone()),
-        None => Err(BuildError::ConfigError(format!("{} is not defined.", key)))?
+        None => Err(config_error(m, key, format!("{} is not defined.", key)))?
     }
 }
 
 ///
-/// Get the artifact type if it is necessary or use the default "lib" if a conanfile is specified.
-/// 
-fn get_artifact_type(m: &Module) -> Result<String, Error> {
-    if let Some(v) = m.config().get(&CONAN_ARTIFACT_TYPE.to_string()) {
-        Ok(v.clone())
-    } else if let Some(_) = m.config().get(&CONAN_CONANFILE_PATH_PROPERTY.to_string()) {
-        Ok(CONAN_ARTIFACT_TYPE_LIB.to_string())
-    } else {
-        Err(BuildError::ConfigError(format!("{} must be defined.", CONAN_ARTIFACT_TYPE)))?
+/// Get the conan reference (name/version@user/channel) of a module if it produces a library. Fails if the version,
+/// user or channel of a library is not defined, for example in one with its own conanfile, since the modules depending
+/// on it could not require it.
+///
+fn get_conan_reference(m: &Module) -> Result<Option<String>, Error> {
+    let artifact_type = get_artifact_type(m)?;
+    if artifact_type != CONAN_ARTIFACT_TYPE_LIB && artifact_type != CONAN_ARTIFACT_TYPE_HEADER {
+        return Ok(None);
     }
-}
 
-impl CleanTask {
-    fn new(m: &Module) -> Result<CleanTask, Error> {
-        // TODO: This should not be duplicated code.
-        let module_path = match dunce::canonicalize(&m.module_dir()) { Ok(a) => a, Err(e) => return Err(BuildError::IOError(format!("Error canonicalizing module path {}: {}", m.module_dir().display(), e.to_string())))? };
-        let build_dir = path_util::PathBuilder::from(&module_path).push(CONAN_BUILD_DIRECTORY).build();
+    let config = m.config();
+    let name = config.get(CONAN_NAME_PROPERTY).unwrap_or(m.name());
+    let version = get_reference_config(m, CONAN_VERSION_PROPERTY)?;
+    let user = get_reference_config(m, CONAN_USER_PROPERTY)?;
+    let channel = get_reference_config(m, CONAN_CHANNEL_PROPERTY)?;
+    Ok(Some(format!("{}/{}@{}/{}", name, version, user, channel)))
+}
 
-        Ok(
-            CleanTask {
-                name: String::from(CONAN_CLEAN_TASK),
-                module_path: module_path,
-                build_dir: build_dir
-            }
-        )
+fn get_reference_config(m: &Module, key: &str) -> Result<String, Error> {
+    match m.config().get(key) {
+        Some(value) => Ok(value.clone()),
+        None => Err(config_error(m, key, format!("{} must be defined for other modules to require {}.", key, m.name())))?
     }
 }
 
-impl Task for CleanTask {
+///
+/// Merges the requires derived from module dependencies into the explicitly configured requires. Explicit requires
+/// take precedence over derived requires for the same package.
+///
+fn merge_requires(explicit: Option<&String>, derived: &[String]) -> String {
+    let package = |reference: &str| reference.split('/').next().unwrap_or("").trim().to_string();
 
-    fn needs_execution(&self) -> bool {
-        true
-    }
+    let mut requires : Vec<String> = match explicit {
+        Some(e) => e.split(',').map(|r| r.trim().to_string()).filter(|r| !r.is_empty()).collect(),
+        None => vec!()
+    };
+    let packages : HashSet<String> = requires.iter().map(|r| package(r)).collect();
 
-    fn execute(&self) -> Result<(), Error> {
-        if !self.build_dir.exists() {
-            return Ok(());
+    for reference in derived {
+        if !packages.contains(&package(reference)) {
+            requires.push(reference.clone());
         }
+    }
+
+    requires.join(",")
+}
+
+///
+/// The conan settings of the module ordered by name, build_type first.
+///
+fn get_settings(m: &Module) -> Result<Vec<(String, String)>, Error> {
+    let mut settings = vec!();
+    if let Some(build_type) = m.config_value(CONAN_BUILD_TYPE_PROPERTY) {
+        settings.push(("build_type".to_string(), build_type.to_string()));
+    }
+    for (k, v) in get_config_map(m, CONAN_SETTINGS_PROPERTY)? {
+        settings.push((k.clone(), v.to_string()));
+    }
+    Ok(settings)
+}
+
+///
+/// Gets the paths of a conan module that are not part of its fingerprint: the build directories and the compile
+/// database of a module in the project root.
+///
+fn get_excluded_paths(m: &Module, module_path: &PathBuf) -> Vec<PathBuf> {
+    let mut excluded = get_build_dirs(m, module_path);
+    excluded.push(module_path.join(COMPILE_COMMANDS_FILE));
+    excluded
+}
+
+///
+/// Renders the conan options as the python tuple assigned to default_options in the default conanfile.
+///
+fn get_default_options(m: &Module) -> Result<String, Error> {
+    let python_value = |v: &ConfigValue| match v {
+        ConfigValue::Bool(true) => "True".to_string(),
+        ConfigValue::Bool(false) => "False".to_string(),
+        v => v.to_string()
+    };
 
-        match fs::remove_dir_all(&self.build_dir) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(BuildError::IOError(format!("Unable to remove directory {:?}: {}", &self.build_dir, e)))?
+    let mut options = vec!();
+    let mut shared = "False".to_string();
+    for (k, v) in get_config_map(m, CONAN_OPTIONS_PROPERTY)? {
+        if k == "shared" {
+            shared = python_value(v);
+        } else {
+            options.push(format!("\"{}={}\"", k, python_value(v)));
         }
     }
+    options.insert(0, format!("\"shared={}\"", shared));
 
+    Ok(format!("({},)", options.join(", ")))
 }
 
-impl BuildTask {
+///
+/// Get the artifact type if it is necessary or use the default "lib" if a conanfile is specified.
+/// 
+fn get_artifact_type(m: &Module) -> Result<String, Error> {
+    if let Some(v) = m.config().get(&CONAN_ARTIFACT_TYPE.to_string()) {
+        Ok(v.clone())
+    } else if let Some(_) = m.config().get(&CONAN_CONANFILE_PATH_PROPERTY.to_string()) {
+        Ok(CONAN_ARTIFACT_TYPE_LIB.to_string())
+    } else {
+        Err(config_error(m, CONAN_ARTIFACT_TYPE, format!("{} must be defined.", CONAN_ARTIFACT_TYPE)))?
+    }
+}
 
-    fn new(m: &Module) -> Result<BuildTask, Error> {
-        let mut config = m.config().clone();
+impl BuildTask {
 
-        let module_path = match dunce::canonicalize(&m.module_dir()) { Ok(a) => a, Err(e) => Err(BuildError::IOError(format!("Error canonicalizing module path {}: {}", m.module_dir().display(), e.to_string())))? };
-        let build_dir = path_util::PathBuilder::from(&module_path).push(CONAN_BUILD_DIRECTORY).build();
+    fn new(m: &Module, requires: &[String], templates: &Templates) -> Result<BuildTask, Error> {
+        let module_path = get_module_path(m)?;
+        let build_dir = get_build_dir(m, &module_path);
+        let config = m.config().clone();
+        let data = get_template_data(m, &module_path, requires)?;
         let conanfile = match config.get(CONAN_CONANFILE_PATH_PROPERTY) {
             Some(p) => PathBuf::from(p),
             None => build_dir.join("conanfile.py")
         };
 
-        if !config.contains_key(CONAN_NAME_PROPERTY) {
-            config.insert(CONAN_NAME_PROPERTY.to_string(), m.name().clone());
-        }
-        if !config.contains_key(CONAN_MODULE_PATH_PROPERTY) {
-            let module_path_string = match module_path.to_str() { Some(a) => a.to_string(), None => Err(BuildError::IOError(format!("Unable to convert {} to utf-8 string.", module_path.display())))? };
-            config.insert(CONAN_MODULE_PATH_PROPERTY.to_string(), module_path_string);
-        }
-        if !config.contains_key(CONAN_REQUIRES_PROPERTY) {
-            config.insert(CONAN_REQUIRES_PROPERTY.to_string(), String::new());
-        }
-
         Ok(
             BuildTask {
                 name : CONAN_BUILD_TASK.to_string(),
                 module_path: module_path.clone(),
                 build_dir: build_dir,
+                excluded: get_excluded_paths(m, &module_path),
                 conanfile: conanfile,
                 artifact_type: get_artifact_type(&m)?,
                 user: get_required_config(&m, CONAN_USER_PROPERTY)?,
                 channel: get_required_config(&m, CONAN_CHANNEL_PROPERTY)?,
-                config: config
+                settings: get_settings(&m)?,
+                config: config,
+                templates: templates.clone(),
+                data: data
             }
         )
     }
 
-    fn get_timestamp_file_path(&self) -> PathBuf {
-        let mut timestamp_path = PathBuf::from(&self.module_path);
-        timestamp_path.push("build");
-        timestamp_path.push("timestamp");
-        timestamp_path
-    }
-
-    fn set_timestamp_file(&self) -> Result<(),Error> {
-        let timestamp_path = self.get_timestamp_file_path();
-        if !timestamp_path.parent().unwrap().exists() {
-            if let Err(e) = fs::create_dir(timestamp_path.parent().unwrap()) {
-                warn!("Error creating timestamp file {}: {}", self.get_timestamp_file_path().display(), e);
-                Err(BuildError::IOError(format!("Error creating timestamp file {}: {}", self.get_timestamp_file_path().display(), e)))?
-            }
-        }
-        if let Err(e) = fs::File::create(self.get_timestamp_file_path()) {
-            warn!("Error creating timestamp file {}: {}", self.get_timestamp_file_path().display(), e);
-            Err(BuildError::IOError(format!("Error creating timestamp file {}: {}", self.get_timestamp_file_path().display(), e)))?
-        }
-        Ok(())
-    }
-
     fn ensure_clean_build_dir(&self) -> Result<(), Error> {
         if self.build_dir.exists() && self.build_dir.is_dir() {
             if let Err(e) = fs::remove_dir_all(&self.build_dir) {
@@ -390,20 +621,18 @@ impl BuildTask {
         Ok(())
     }
 
+    ///
+    /// Renders the conanfile and CMakeLists.txt templates with the module configuration.
+    ///
+    fn render_build_scripts(&self) -> Result<(String, String), Error> {
+        Ok((
+            self.templates.render(&self.module_path, CONAN_CONANFILE_TEMPLATE, &self.data)?,
+            self.templates.render(&self.module_path, CONAN_CMAKELISTS_TEMPLATE, &self.data)?
+        ))
+    }
+
     fn write_build_scripts(&self) -> Result<(), Error> {
-        let mut conanfile = String::from(include_str!("scripts/conan/conanfile.py"));
-        let mut cmakelists = String::from(include_str!("scripts/conan/CMakeLists.txt"));
-
-        // This could be much more efficient but it isnt a bottleneck.
-        for (k,v) in &self.config {
-            // This ends up replacing ("${key}" with the value)
-            let p = format!("${}{}{}", "{", k, "}");
-            debug!("Replacing: {}", p);
-            conanfile = conanfile.replace(p.as_str(), v);
-        }
-        for (k,v) in &self.config {
-            cmakelists = cmakelists.replace(format!("${}{}{}", "{", k, "}").as_str(), v);
-        }
+        let (conanfile, cmakelists) = self.render_build_scripts()?;
 
         // Ensure path is a directory.
         {
@@ -439,70 +668,32 @@ impl BuildTask {
 impl Task for BuildTask {
 
     fn needs_execution(&self) -> bool {
-        use walkdir::WalkDir;
+        // Only used when fingerprints are unavailable.
+        true
+    }
 
-        let timestamp_path = self.get_timestamp_file_path();
-        trace!("Looking for timestamp file in: {:?}", timestamp_path);
+    fn fingerprint(&self) -> Result<Option<String>, Error> {
+        let mut fingerprint = Fingerprint::new();
 
-        if !timestamp_path.exists() {
-            debug!("No timestamp found at {}. {} needs rebuild.", self.name, timestamp_path.display());
-            return true;
-        }
+        add_build_dir_state(&mut fingerprint, &self.build_dir);
 
-        let metadata = fs::metadata(timestamp_path.clone());
-        if let Err(e) = metadata {
-            warn!("Error accessing metadata for {}: {}", timestamp_path.display(), e);
-            return true
+        let mut config : Vec<(&String, &String)> = self.config.iter().collect();
+        config.sort();
+        for (k,v) in config {
+            fingerprint.add(k).add(v);
         }
 
-        let modified = metadata.unwrap().modified();
-        if let Err(e) = modified {
-            warn!("Error accessing modified time for {}: {}", timestamp_path.display(), e);
-            return true
+        let (conanfile, cmakelists) = self.render_build_scripts()?;
+        if self.conanfile.exists() {
+            fingerprint.add_file(&self.conanfile)?;
+        } else {
+            fingerprint.add(conanfile);
         }
+        fingerprint.add(cmakelists);
 
-        let timestamp = modified.unwrap();
-
-        for entry in WalkDir::new(&self.module_path).into_iter().filter_entry(
-            |e| {
-                !(e.depth() == 1 && e.file_name() == CONAN_BUILD_DIRECTORY) && // Ignore the build directory.
-                !(e.depth() == 2 && e.file_name() == CONAN_BUILD_DIRECTORY && e.path().parent().unwrap().file_name().unwrap() == CONAN_TEST_DIRECTORY) // Ignore the test build directory.
-            }
-        ) {
-            if let Err(e) = entry {
-                warn!("Error accessing path: {}", e);
-                continue
-            }
-
-            trace!("Examining: {}", entry.as_ref().unwrap().path().display());
+        add_module_files(&mut fingerprint, &self.module_path, &self.excluded)?;
 
-            let metadata = fs::metadata(entry.as_ref().unwrap().path());
-            if let Err(e) = metadata {
-                warn!("Error accessing metadata for {}: {}", entry.unwrap().path().display(), e);
-                continue
-            }
-
-            let modified = metadata.unwrap().modified();
-            if let Err(e) = modified {
-                warn!("Error accessing modified time for {}: {}", entry.unwrap().path().display(), e);
-                continue
-            }
-
-            let modified = modified.unwrap();
-            if let Ok(t) = modified.duration_since(timestamp) {
-                debug!(
-                    "File {} was modified on {}, {} since timestamp {}.",
-                    entry.unwrap().path().display(),
-                    DateTime::<chrono::offset::Local>::from(modified),
-                    timeago::Formatter::new().convert(t),
-                    DateTime::<chrono::offset::Local>::from(timestamp)
-                );
-                return true;
-            }
-
-        }
-
-        false
+        Ok(Some(fingerprint.finish()))
     }
 
     fn execute(&self) -> Result<(), Error> {
@@ -527,7 +718,20 @@ impl Task for BuildTask {
             let config = ConanConfig::Install(InstallConfig {
                 wd: wd.clone(),
                 conanfile: self.conanfile.clone(),
-                install_folder: self.build_dir.clone()
+                install_folder: self.build_dir.clone(),
+                settings: self.settings.clone()
+            });
+            conan(config)?;
+
+            // Libraries are built in the conan cache so configure them in the build directory as well to get their
+            // compile database.
+            let config = ConanConfig::Build(BuildConfig {
+                wd: wd.clone(),
+                conanfile: self.conanfile.clone(),
+                source_folder: self.build_dir.clone(),
+                install_folder: self.build_dir.clone(),
+                build_folder: self.build_dir.clone(),
+                configure_only: true
             });
             conan(config)?;
 
@@ -535,17 +739,17 @@ impl Task for BuildTask {
                 wd: wd,
                 conanfile: self.conanfile.clone(),
                 user: user.clone(),
-                channel: channel.clone()
+                channel: channel.clone(),
+                settings: self.settings.clone()
             });
-            conan(config)?;
-            self.set_timestamp_file()
-
+            conan(config)
         } else if artifact_type == CONAN_ARTIFACT_TYPE_BIN {
 
             let config = ConanConfig::Install(InstallConfig {
                 wd: wd.clone(),
                 conanfile: self.conanfile.clone(),
-                install_folder: self.build_dir.clone()
+                install_folder: self.build_dir.clone(),
+                settings: self.settings.clone()
             });
             conan(config)?;
 
@@ -554,17 +758,230 @@ impl Task for BuildTask {
                 conanfile: self.conanfile.clone(),
                 source_folder: self.build_dir.clone(),
                 install_folder: self.build_dir.clone(),
-                build_folder: self.build_dir.clone()
+                build_folder: self.build_dir.clone(),
+                configure_only: false
             });
-            conan(config)?;
-            self.set_timestamp_file()
+            conan(config)
+        } else if artifact_type == CONAN_ARTIFACT_TYPE_HEADER {
 
+            // There is nothing to compile, the package only contains the headers.
+            let config = ConanConfig::Create(CreateConfig {
+                wd: wd,
+                conanfile: self.conanfile.clone(),
+                user: user.clone(),
+                channel: channel.clone(),
+                settings: self.settings.clone()
+            });
+            conan(config)
         } else {
             Err(BuildError::IOError(format!("Unknown artifact type {}.", artifact_type)))?
         }
     }
 }
 
+fn settings_args(settings: &[(String, String)]) -> Vec<String> {
+    let mut args = vec!();
+    for (k, v) in settings {
+        args.push("-s".to_string());
+        args.push(format!("{}={}", k, v));
+    }
+    args
+}
+
+impl CompdbTask {
+
+    fn new(modules: Vec<(PathBuf, PathBuf)>) -> CompdbTask {
+        CompdbTask {
+            // Tasks run in the project root.
+            output: PathBuf::from(COMPILE_COMMANDS_FILE),
+            modules: modules
+        }
+    }
+
+    fn databases(&self) -> Vec<(PathBuf, &PathBuf, &PathBuf)> {
+        self.modules
+            .iter()
+            .map(|(build_dir, module_path)| (build_dir.join(COMPILE_COMMANDS_FILE), build_dir, module_path))
+            .filter(|(database, _, _)| database.is_file())
+            .collect()
+    }
+}
+
+///
+/// Points the paths of a compile database entry at the sources of the module instead of the copies the conanfile
+/// makes in the build directory.
+///
+fn map_compile_command(entry: Value, build_dir: &PathBuf, module_path: &PathBuf) -> Value {
+    let from = build_dir.join("src").display().to_string();
+    let to = module_path.join("src").display().to_string();
+    match entry {
+        Value::String(s) => Value::String(s.replace(&from, &to)),
+        Value::Array(a) => Value::Array(a.into_iter().map(|v| map_compile_command(v, build_dir, module_path)).collect()),
+        Value::Object(o) => Value::Object(o.into_iter().map(|(k, v)| (k, map_compile_command(v, build_dir, module_path))).collect()),
+        v => v
+    }
+}
+
+impl Task for CompdbTask {
+
+    fn needs_execution(&self) -> bool {
+        true
+    }
+
+    fn fingerprint(&self) -> Result<Option<String>, Error> {
+        // The merged database is part of the fingerprint so that it is written again if it was removed or edited.
+        let mut fingerprint = Fingerprint::new();
+        if self.output.is_file() {
+            fingerprint.add_file(&self.output)?;
+        } else {
+            fingerprint.add("missing");
+        }
+        for (database, _, _) in self.databases() {
+            fingerprint.add(database.to_string_lossy().as_bytes());
+            fingerprint.add_file(&database)?;
+        }
+        Ok(Some(fingerprint.finish()))
+    }
+
+    fn execute(&self) -> Result<(), Error> {
+        let mut entries : Vec<Value> = vec!();
+        for (database, build_dir, module_path) in self.databases() {
+            debug!("Merging: {:?}", database);
+            let contents = match fs::read(&database) {
+                Ok(c) => c,
+                Err(e) => Err(BuildError::IOError(format!("Unable to read {:?}: {}", database, e)))?
+            };
+            let module_entries : Vec<Value> = match serde_json::from_slice(&contents) {
+                Ok(e) => e,
+                Err(e) => Err(BuildError::IOError(format!("Invalid compile database {:?}: {}", database, e)))?
+            };
+            entries.extend(module_entries.into_iter().map(|e| map_compile_command(e, build_dir, module_path)));
+        }
+
+        debug!("Writing: {:?}", self.output);
+        let contents = match serde_json::to_string_pretty(&entries) {
+            Ok(c) => c,
+            Err(e) => Err(BuildError::IOError(format!("Unable to serialize the compile database: {}", e)))?
+        };
+        if let Err(e) = fs::write(&self.output, contents) { Err(BuildError::IOError(format!("Unable to write {:?}: {}", self.output, e)))? }
+        Ok(())
+    }
+}
+
+impl TestTask {
+
+    fn new(m: &Module, requires: &[String], templates: &Templates) -> Result<TestTask, Error> {
+        let module_path = get_module_path(m)?;
+        let test_dir = get_build_dir(m, &module_path).join(CONAN_TEST_DIRECTORY);
+        let config = m.config();
+        let mut data = get_template_data(m, &module_path, requires)?;
+
+        // The tests link to the library of the module itself, to everything it requires and to Catch2.
+        let mut derived : Vec<String> = get_conan_reference(m)?.into_iter().collect();
+        derived.extend(split_requires(&merge_requires(config.get(CONAN_REQUIRES_PROPERTY), requires)));
+        let test_requires = config.get(CONAN_TEST_REQUIRES_PROPERTY).cloned().unwrap_or(CONAN_DEFAULT_TEST_REQUIRES.to_string());
+        template::set(&mut data, CONAN_TEST_REQUIRES_PROPERTY, Value::from(split_requires(&merge_requires(Some(&test_requires), &derived))));
+
+        let name = config.get(CONAN_NAME_PROPERTY).unwrap_or(m.name());
+        let binary = test_dir.join("bin").join(format!("{}_test{}", name, consts::EXE_SUFFIX));
+        Ok(
+            TestTask {
+                excluded: get_excluded_paths(m, &module_path),
+                module_path: module_path,
+                report: test_dir.join(CONAN_TEST_REPORT),
+                test_dir: test_dir,
+                binary: binary,
+                settings: get_settings(&m)?,
+                templates: templates.clone(),
+                data: data
+            }
+        )
+    }
+
+    ///
+    /// Renders the conanfile and CMakeLists.txt that build the tests.
+    ///
+    fn render_build_scripts(&self) -> Result<(String, String), Error> {
+        Ok((
+            self.templates.render(&self.module_path, CONAN_TEST_CONANFILE_TEMPLATE, &self.data)?,
+            self.templates.render(&self.module_path, CONAN_TEST_CMAKELISTS_TEMPLATE, &self.data)?
+        ))
+    }
+}
+
+impl Task for TestTask {
+
+    fn needs_execution(&self) -> bool {
+        true
+    }
+
+    fn fingerprint(&self) -> Result<Option<String>, Error> {
+        let mut fingerprint = Fingerprint::new();
+
+        // The build task of the module removes the test build directory, so it is not part of the fingerprint.
+        fingerprint.add(self.data.to_string());
+
+        let (conanfile, cmakelists) = self.render_build_scripts()?;
+        fingerprint.add(conanfile).add(cmakelists);
+
+        add_module_files(&mut fingerprint, &self.module_path, &self.excluded)?;
+
+        Ok(Some(fingerprint.finish()))
+    }
+
+    fn execute(&self) -> Result<(), Error> {
+        if self.test_dir.exists() {
+            if let Err(e) = fs::remove_dir_all(&self.test_dir) {
+                Err(BuildError::IOError(format!("Error deleting test build directory at {:?}: {}", self.test_dir, e)))?
+            }
+        }
+        if let Err(e) = fs::create_dir_all(&self.test_dir) {
+            Err(BuildError::IOError(format!("Error creating test build directory at {:?}: {}", self.test_dir, e)))?
+        }
+
+        let (conanfile, cmakelists) = self.render_build_scripts()?;
+        let conanfile_path = self.test_dir.join("conanfile.py");
+        for (path, contents) in vec!((&conanfile_path, conanfile), (&self.test_dir.join("CMakeLists.txt"), cmakelists)) {
+            debug!("Writing: {:?}", path);
+            if let Err(e) = fs::write(path, contents) { Err(BuildError::IOError(format!("Unable to write {:?}: {}", path, e)))? }
+        }
+
+        conan(ConanConfig::Install(InstallConfig {
+            wd: self.module_path.clone(),
+            conanfile: conanfile_path.clone(),
+            install_folder: self.test_dir.clone(),
+            settings: self.settings.clone()
+        }))?;
+        conan(ConanConfig::Build(BuildConfig {
+            wd: self.module_path.clone(),
+            conanfile: conanfile_path,
+            source_folder: self.test_dir.clone(),
+            install_folder: self.test_dir.clone(),
+            build_folder: self.test_dir.clone(),
+            configure_only: false
+        }))?;
+
+        let mut cmd = Command::new(&self.binary);
+        cmd
+            .arg("--reporter").arg("junit")
+            .arg("--out").arg(&self.report)
+            .current_dir(&self.module_path);
+
+        debug!("Command: {:?}", cmd);
+
+        match cmd.status() {
+            Ok(status) => {
+                debug!("Status: {:?}", status);
+                if !status.success() {
+                    Err(BuildError::ExecutionError(format!("Tests failed: {}. The results are in {}.", status, self.report.display())))?
+                }
+                Ok(())
+            },
+            Err(e) => Err(BuildError::ExecutionError(format!("Unable to run {}: {}", self.binary.display(), e)))?
+        }
+    }
+}
+
 fn conan(config: ConanConfig) -> Result<(), Error> {
 
     debug!("Config: {:#?}", config);
@@ -576,6 +993,7 @@ fn conan(config: ConanConfig) -> Result<(), Error> {
                 .arg("create")
                 .arg(config.conanfile)
                 .arg(format!("{}/{}", config.user, config.channel))
+                .args(settings_args(&config.settings))
                 .current_dir(config.wd)
         },
         ConanConfig::Install(config) => {
@@ -584,6 +1002,7 @@ fn conan(config: ConanConfig) -> Result<(), Error> {
                 .arg(config.conanfile)
                 .arg("--build=missing")
                 .arg(format!("--install-folder={}", config.install_folder.display()))
+                .args(settings_args(&config.settings))
                 .current_dir(config.wd)
         },
         ConanConfig::Build(config) => {
@@ -593,6 +1012,7 @@ fn conan(config: ConanConfig) -> Result<(), Error> {
                 .arg(format!("--source-folder={}", config.source_folder.display()))
                 .arg(format!("--install-folder={}", config.install_folder.display()))
                 .arg(format!("--build-folder={}", config.build_folder.display()))
+                .args(if config.configure_only { vec!("--configure") } else { vec!() })
                 .current_dir(config.wd)
         }
     };
@@ -617,4 +1037,170 @@ fn conan(config: ConanConfig) -> Result<(), Error> {
             Err(BuildError::ExecutionError(format!("Error executing process: {}", e)))?
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn merged_requires() {
+        let derived = vec!("hellolib/0.1@helloworld/stable".to_string(), "otherlib/0.2@helloworld/stable".to_string());
+
+        assert_eq!(merge_requires(None, &[]), "");
+        assert_eq!(
+            merge_requires(None, &derived),
+            "hellolib/0.1@helloworld/stable,otherlib/0.2@helloworld/stable"
+        );
+        assert_eq!(
+            merge_requires(Some(&"zlib/1.2.11@conan/stable, hellolib/0.0@me/testing".to_string()), &derived),
+            "zlib/1.2.11@conan/stable,hellolib/0.0@me/testing,otherlib/0.2@helloworld/stable"
+        );
+    }
+
+    fn map(entries: Vec<(&str, ConfigValue)>) -> ConfigValue {
+        ConfigValue::Map(entries.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+    }
+
+    #[test]
+    fn config_maps() {
+        let config = map(vec!(
+            ("conan", map(vec!(
+                ("options", map(vec!(("shared", ConfigValue::Bool(true)), ("zlib:minizip", ConfigValue::Bool(false)))))
+            ))),
+            ("cmake.definitions", map(vec!(
+                ("USE_SSL", ConfigValue::Bool(true)),
+                ("NAMES", ConfigValue::List(vec!(ConfigValue::String("a".to_string()), ConfigValue::String("b".to_string()))))
+            ))),
+            ("cmake.flags", map(vec!(("gcc", ConfigValue::String("-Wall -Wextra".to_string())))))
+        ));
+        let m = Module::new("m", PathBuf::new(), PathBuf::new(), HashSet::new(), config);
+
+        assert_eq!(get_default_options(&m).unwrap(), "(\"shared=True\", \"zlib:minizip=False\",)");
+        assert_eq!(
+            get_cmake_definitions(&m).unwrap(),
+            vec!(("NAMES".to_string(), "a;b".to_string()), ("USE_SSL".to_string(), "ON".to_string()))
+        );
+        assert_eq!(get_cmake_flags(&m).unwrap(), vec!(("GNU".to_string(), "-Wall -Wextra".to_string())));
+
+        let empty = Module::new("m", PathBuf::new(), PathBuf::new(), HashSet::new(), ConfigValue::new());
+        assert_eq!(get_default_options(&empty).unwrap(), "(\"shared=False\",)");
+        assert!(get_cmake_definitions(&empty).unwrap().is_empty());
+    }
+
+    #[test]
+    fn render() {
+        let config = map(vec!(
+            ("conan", map(vec!(
+                ("version", ConfigValue::String("0.1".to_string())),
+                ("artifact_type", ConfigValue::String("lib".to_string()))
+            ))),
+            ("cmake.definitions", map(vec!(("GREETING", ConfigValue::String("say \"hi\"".to_string())))))
+        ));
+        let m = Module::new("hello", PathBuf::new(), PathBuf::new(), HashSet::new(), config);
+        let mut data = get_template_data(&m, &PathBuf::from("/p/hello"), &["a/1@u/c".to_string()]).unwrap();
+        let templates = Templates::new(vec!());
+        let module_path = PathBuf::from("/p/hello");
+
+        let conanfile = templates.render(&module_path, CONAN_CONANFILE_TEMPLATE, &data).unwrap();
+        assert!(conanfile.contains("    name = \"hello\"\n    version = \"0.1\"\n"));
+        assert!(conanfile.contains("    requires = (\"a/1@u/c\", )\n"));
+        assert!(conanfile.contains("        self.cpp_info.libs=[\"hello\"]\n"));
+
+        let cmakelists = templates.render(&module_path, CONAN_CMAKELISTS_TEMPLATE, &data).unwrap();
+        assert!(cmakelists.contains("set(GREETING \"say \\\"hi\\\"\")\n"));
+        assert!(cmakelists.contains("set(ARTIFACT_NAME hello)\nadd_library(${ARTIFACT_NAME} ${SRC})\n"));
+
+        template::set(&mut data, CONAN_ARTIFACT_TYPE, Value::from(CONAN_ARTIFACT_TYPE_HEADER));
+        let conanfile = templates.render(&module_path, CONAN_CONANFILE_TEMPLATE, &data).unwrap();
+        assert!(conanfile.contains("        self.info.header_only()\n"));
+        assert!(!conanfile.contains("cmake.build()"));
+        assert!(!conanfile.contains("self.cpp_info.libs"));
+        let cmakelists = templates.render(&module_path, CONAN_CMAKELISTS_TEMPLATE, &data).unwrap();
+        assert!(cmakelists.contains("add_library(${ARTIFACT_NAME} INTERFACE)\n"));
+        assert!(!cmakelists.contains("add_library(${ARTIFACT_NAME} ${SRC})"));
+    }
+
+    #[test]
+    fn artifact_types() {
+        let plugin = ConanPlugin::new(&Templates::new(vec!()));
+        let module = |artifact_type: &str, user: Option<&str>| {
+            let mut config = vec!(
+                ("conan.version", ConfigValue::String("0.1".to_string())),
+                ("conan.artifact_type", ConfigValue::String(artifact_type.to_string())),
+                ("conan.artifact_name", ConfigValue::String("hello".to_string())),
+                ("conan.channel", ConfigValue::String("stable".to_string()))
+            );
+            if let Some(user) = user {
+                config.push(("conan.user", ConfigValue::String(user.to_string())));
+            }
+            Module::new("hello", PathBuf::new(), PathBuf::new(), HashSet::new(), map(config))
+        };
+
+        assert!(plugin.validate_config(&module("bin", None)).is_ok());
+        assert!(plugin.validate_config(&module("lib", None)).is_err());
+        assert!(plugin.validate_config(&module("header", None)).is_err());
+        assert!(plugin.validate_config(&module("header", Some("user"))).is_ok());
+        assert!(plugin.validate_config(&module("shared", Some("user"))).is_err());
+
+        assert_eq!(get_conan_reference(&module("header", Some("user"))).unwrap(), Some("hello/0.1@user/stable".to_string()));
+        assert_eq!(get_conan_reference(&module("bin", Some("user"))).unwrap(), None);
+        let error = get_conan_reference(&module("lib", None)).unwrap_err().to_string();
+        assert!(error.contains("Module hello: conan.user must be defined"), "{}", error);
+    }
+
+    #[test]
+    fn compile_command_paths() {
+        let entry : Value = serde_json::from_str(
+            r#"{"directory": "/p/m/build", "command": "c++ -I/p/m/build/src -c /p/m/build/src/a.cpp", "file": "/p/m/build/src/a.cpp"}"#
+        ).unwrap();
+        let mapped = map_compile_command(entry, &PathBuf::from("/p/m/build"), &PathBuf::from("/p/m"));
+
+        assert_eq!(mapped["directory"], "/p/m/build");
+        assert_eq!(mapped["command"], "c++ -I/p/m/src -c /p/m/src/a.cpp");
+        assert_eq!(mapped["file"], "/p/m/src/a.cpp");
+    }
+
+    #[test]
+    fn test_package() {
+        let dir = env::temp_dir().join(format!("mib-conan-test-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let module_path = dunce::canonicalize(&dir).unwrap();
+        let module = |test_requires: Option<&str>| {
+            let mut config = vec!(
+                ("conan.version", ConfigValue::String("0.1".to_string())),
+                ("conan.user", ConfigValue::String("u".to_string())),
+                ("conan.channel", ConfigValue::String("c".to_string())),
+                ("conan.artifact_type", ConfigValue::String("lib".to_string())),
+                ("conan.artifact_name", ConfigValue::String("hello".to_string())),
+                ("cmake.flags", map(vec!(("gcc", ConfigValue::String("-Wall".to_string())))))
+            );
+            if let Some(test_requires) = test_requires {
+                config.push(("conan.test_requires", ConfigValue::String(test_requires.to_string())));
+            }
+            Module::new("hello", dir.clone(), dir.join("build"), HashSet::new(), map(config))
+        };
+        let templates = Templates::new(vec!());
+        let requires = ["a/1@u/c".to_string()];
+
+        // The tests require Catch2, the module itself and what it requires.
+        let task = TestTask::new(&module(None), &requires, &templates).unwrap();
+        let (conanfile, cmakelists) = task.render_build_scripts().unwrap();
+        assert!(conanfile.contains("    name = \"hello_test\"\n"), "{}", conanfile);
+        assert!(conanfile.contains("    requires = (\"Catch2/2.5.2@catchorg/stable\", \"hello/0.1@u/c\", \"a/1@u/c\", )\n"), "{}", conanfile);
+        assert!(cmakelists.contains("project(hello_test CXX)\n"), "{}", cmakelists);
+        assert!(cmakelists.contains(&format!("set(MODULE_DIR \"{}\")\n", module_path.display())), "{}", cmakelists);
+        assert!(cmakelists.contains("if(CMAKE_CXX_COMPILER_ID STREQUAL \"GNU\")\n\tadd_compile_options(-Wall)\n"), "{}", cmakelists);
+        assert!(cmakelists.contains("add_executable(${PROJECT_NAME} ${CMAKE_BINARY_DIR}/catch_main.cpp ${TEST_SRC})\n"), "{}", cmakelists);
+
+        // Configured test requires take precedence over the derived ones for the same package.
+        let task = TestTask::new(&module(Some("gtest/1.8@x/y, a/2@u/c")), &requires, &templates).unwrap();
+        let (conanfile, _) = task.render_build_scripts().unwrap();
+        assert!(conanfile.contains("    requires = (\"gtest/1.8@x/y\", \"a/2@u/c\", \"hello/0.1@u/c\", )\n"), "{}", conanfile);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
\ No newline at end of file
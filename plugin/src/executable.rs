@@ -0,0 +1,403 @@
+use ::Plugin;
+use build::BuildError;
+use build::Context;
+use build::Task;
+use failure::Error;
+use os_pipe;
+use os_pipe::IntoStdio;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::thread;
+use template;
+
+const JSONRPC_VERSION: &'static str = "2.0";
+
+const METHOD_GET_DOCUMENTATION: &'static str = "get_documentation";
+const METHOD_CONFIGURE: &'static str = "configure";
+const METHOD_NEEDS_EXECUTION: &'static str = "needs_execution";
+const METHOD_EXECUTE: &'static str = "execute";
+
+///
+/// A plugin implemented by an executable.
+///
+/// For every call the executable is started, receives a single JSON-RPC request on stdin and must write a single
+/// JSON-RPC response to stdout before exiting. Anything written to stderr is passed through to the user.
+///
+pub struct ExecutablePlugin {
+    path: PathBuf
+}
+
+struct ExecutableTask {
+    plugin: PathBuf,
+    name: String,
+    data: Value,
+    // Why the plugin could not tell whether the task needs execution. Executing the task then fails with it.
+    needs_execution_error: Mutex<Option<String>>
+}
+
+#[derive(Serialize)]
+struct Request<'a, P: Serialize> {
+    jsonrpc: &'a str,
+    id: u64,
+    method: &'a str,
+    params: P
+}
+
+#[derive(Deserialize)]
+struct Response {
+    result: Option<Value>,
+    error: Option<ResponseError>
+}
+
+#[derive(Deserialize)]
+struct ResponseError {
+    code: i64,
+    message: String
+}
+
+#[derive(Serialize)]
+struct ConfigureParams {
+    modules: Vec<ModuleDescription>
+}
+
+#[derive(Serialize)]
+struct ModuleDescription {
+    name: String,
+    types: Vec<String>,
+    module_dir: PathBuf,
+    build_dir: PathBuf,
+    deps: Vec<String>,
+    // The flat view of the configuration.
+    config: HashMap<String, String>,
+    // The configuration as written in the build file.
+    config_tree: Value
+}
+
+#[derive(Deserialize)]
+struct ConfigureResult {
+    tasks: Vec<TaskDescription>
+}
+
+#[derive(Deserialize)]
+struct TaskDescription {
+    name: String,
+    // Tasks this task depends on.
+    #[serde(default)]
+    dependencies: Vec<String>,
+    // Tasks that depend on this task, for example "build" or "clean".
+    #[serde(default)]
+    dependents: Vec<String>,
+    // Opaque data passed back to the plugin with every call for this task.
+    #[serde(default)]
+    data: Value
+}
+
+#[derive(Serialize)]
+struct TaskParams<'a> {
+    task: &'a str,
+    data: &'a Value
+}
+
+impl ExecutablePlugin {
+    pub fn new(path: &PathBuf) -> ExecutablePlugin {
+        ExecutablePlugin {
+            path: path.clone()
+        }
+    }
+}
+
+impl Plugin for ExecutablePlugin {
+
+    fn name(&self) -> String {
+        match self.path.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => self.path.display().to_string()
+        }
+    }
+
+    fn get_documentation(&self) -> HashMap<String, String> {
+        match call(&self.path, METHOD_GET_DOCUMENTATION, Value::Null) {
+            Ok(doc) => doc,
+            Err(e) => {
+                error!("Error retrieving documentation from plugin {}: {}", self.path.display(), e);
+                HashMap::new()
+            }
+        }
+    }
+
+    fn configure(&self, context: &mut Context) -> Result<(), Error> {
+        let mut modules = vec!();
+        for (name, module) in context.modules_mut() {
+            let mut types : Vec<String> = module.types().iter().cloned().collect();
+            types.sort();
+            modules.push(ModuleDescription {
+                name: name.clone(),
+                types: types,
+                module_dir: module.module_dir().clone(),
+                build_dir: module.build_dir().clone(),
+                deps: vec!(),
+                config: module.config().clone(),
+                config_tree: template::to_json(module.config_tree())
+            });
+        }
+        // Dependencies can only be read once the modules are no longer borrowed.
+        for module in &mut modules {
+            module.deps = context.get_module_deps(&module.name)?.iter().cloned().collect();
+            module.deps.sort();
+        }
+        modules.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let result : ConfigureResult = call(&self.path, METHOD_CONFIGURE, ConfigureParams { modules: modules })?;
+
+        for task in &result.tasks {
+            trace!("Plugin {} adding task: {}", self.path.display(), task.name);
+            context.add_task(&task.name, Box::new(ExecutableTask {
+                plugin: self.path.clone(),
+                name: task.name.clone(),
+                data: task.data.clone(),
+                needs_execution_error: Mutex::new(None)
+            }));
+        }
+
+        // Tasks may depend on each other so they are only connected once all of them have been added.
+        for task in &result.tasks {
+            for dep in &task.dependencies {
+                debug!("Task {} depends on {}.", task.name, dep);
+                context.task_mut(&task.name)?.depends_on(dep)?;
+            }
+            for dependent in &task.dependents {
+                debug!("Task {} depends on {}.", dependent, task.name);
+                context.task_mut(dependent)?.depends_on(&task.name)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Task for ExecutableTask {
+
+    fn needs_execution(&self) -> bool {
+        let params = TaskParams { task: &self.name, data: &self.data };
+        match call(&self.plugin, METHOD_NEEDS_EXECUTION, params) {
+            Ok(needs_execution) => needs_execution,
+            Err(e) => {
+                // A broken plugin must not look like an out of date task, so executing it reports the error.
+                let message = format!("Unable to ask plugin {} whether {} needs execution: {}", self.plugin.display(), self.name, e);
+                error!("{}", message);
+                *self.needs_execution_error.lock().unwrap() = Some(message);
+                true
+            }
+        }
+    }
+
+    fn execute(&self) -> Result<(), Error> {
+        if let Some(message) = self.needs_execution_error.lock().unwrap().take() {
+            Err(BuildError::ExecutionError(message))?
+        }
+
+        let params = TaskParams { task: &self.name, data: &self.data };
+        let _ : Value = call(&self.plugin, METHOD_EXECUTE, params)?;
+        Ok(())
+    }
+}
+
+///
+/// Starts the plugin, sends it a single request and waits for its response.
+///
+fn call<P: Serialize, R: DeserializeOwned>(plugin: &PathBuf, method: &str, params: P) -> Result<R, Error> {
+    let request = Request {
+        jsonrpc: JSONRPC_VERSION,
+        id: 1,
+        method: method,
+        params: params
+    };
+    let request = match serde_json::to_vec(&request) {
+        Ok(r) => r,
+        Err(e) => Err(BuildError::ExecutionError(format!("Unable to encode {} request: {}", method, e)))?
+    };
+
+    let (mut reader, writer) = match os_pipe::pipe() {
+        Ok(p) => p,
+        Err(e) => Err(BuildError::IOError(format!("Unable to create pipe: {}", e)))?
+    };
+
+    let mut child = {
+        // The command holds a copy of the write end which must be closed before reading to see the end of the output.
+        let mut cmd = Command::new(plugin);
+        cmd
+            .arg(method)
+            .stdin(Stdio::piped())
+            .stdout(writer.into_stdio());
+
+        debug!("Command: {:?}", cmd);
+
+        match cmd.spawn() {
+            Ok(c) => c,
+            Err(e) => Err(BuildError::ExecutionError(format!("Unable to start plugin {}: {}", plugin.display(), e)))?
+        }
+    };
+
+    // The request is written while the response is read. A plugin that answers before it has read the whole request
+    // would otherwise block on a full stdout pipe while mib blocks on its full stdin pipe.
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = thread::spawn(move || -> io::Result<()> {
+        stdin.write_all(&request)?;
+        stdin.write_all(b"\n")
+    });
+
+    let mut output = vec!();
+    let read = reader.read_to_end(&mut output);
+    let written = match writer.join() {
+        Ok(w) => w,
+        Err(_) => Err(io::Error::new(io::ErrorKind::Other, "the thread writing the request panicked"))
+    };
+
+    let status = match child.wait() {
+        Ok(s) => s,
+        Err(e) => Err(BuildError::ExecutionError(format!("Error executing plugin {}: {}", plugin.display(), e)))?
+    };
+    if let Err(e) = read {
+        Err(BuildError::IOError(format!("Unable to read response from plugin {}: {}", plugin.display(), e)))?
+    }
+    match written {
+        // The plugin may answer without reading the whole request.
+        Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => debug!("Plugin {} did not read the whole request.", plugin.display()),
+        Err(e) => Err(BuildError::IOError(format!("Unable to write request to plugin {}: {}", plugin.display(), e)))?,
+        Ok(()) => ()
+    }
+    if !status.success() {
+        Err(BuildError::ExecutionError(format!("Plugin {} failed on {}: {}", plugin.display(), method, status)))?
+    }
+
+    let response : Response = match serde_json::from_slice(&output) {
+        Ok(r) => r,
+        Err(e) => Err(BuildError::ExecutionError(format!("Invalid response from plugin {} to {}: {}", plugin.display(), method, e)))?
+    };
+
+    if let Some(e) = response.error {
+        Err(BuildError::ExecutionError(format!("Plugin {} returned error {} for {}: {}", plugin.display(), e.code, method, e.message)))?
+    }
+
+    match serde_json::from_value(response.result.unwrap_or(Value::Null)) {
+        Ok(r) => Ok(r),
+        Err(e) => Err(BuildError::ExecutionError(format!("Unexpected result from plugin {} for {}: {}", plugin.display(), method, e)))?
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::process;
+
+    struct EmptyTask;
+
+    impl Task for EmptyTask {
+        fn needs_execution(&self) -> bool {
+            false
+        }
+        fn execute(&self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    ///
+    /// Writes a plugin that saves each request next to itself and answers each method with the given response.
+    ///
+    fn plugin(dir: &PathBuf, name: &str, responses: &[(&str, &str)]) -> PathBuf {
+        let path = dir.join(name);
+        let mut script = "#!/bin/sh\ncat > \"$0.$1.json\"\ncase \"$1\" in\n".to_string();
+        for (method, response) in responses {
+            script.push_str(&format!("  {}) echo '{}' ;;\n", method, response));
+        }
+        script.push_str("  *) exit 1 ;;\nesac\n");
+        fs::write(&path, script).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    fn plugin_task(plugin: &PathBuf) -> ExecutableTask {
+        ExecutableTask {
+            plugin: plugin.clone(),
+            name: "gen:build".to_string(),
+            data: Value::from("data"),
+            needs_execution_error: Mutex::new(None)
+        }
+    }
+
+    #[test]
+    fn protocol() {
+        let dir = env::temp_dir().join(format!("mib-plugin-test-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        // Every plugin is written before any is started so that none of them is still open for writing.
+        let gen = plugin(&dir, "gen", &[
+            ("configure", r#"{"jsonrpc": "2.0", "id": 1, "result": {"tasks": [{"name": "gen:build", "dependents": ["build"], "data": {"n": 1}}]}}"#),
+            ("needs_execution", r#"{"jsonrpc": "2.0", "id": 1, "result": false}"#),
+            ("execute", r#"{"jsonrpc": "2.0", "id": 1, "result": null}"#)
+        ]);
+        let malformed = plugin(&dir, "malformed", &[("execute", "not json")]);
+        let failing = plugin(&dir, "failing", &[
+            ("execute", r#"{"jsonrpc": "2.0", "id": 1, "error": {"code": 42, "message": "compiler not found"}}"#)
+        ]);
+        let broken = plugin(&dir, "broken", &[("execute", r#"{"jsonrpc": "2.0", "id": 1, "result": null}"#)]);
+        // Answers with more than a pipe buffer before reading a request of more than a pipe buffer.
+        let eager = dir.join("eager");
+        fs::write(&eager, concat!(
+            "#!/bin/sh\n",
+            "printf '{\"jsonrpc\": \"2.0\", \"id\": 1, \"result\": null, \"padding\": \"'\n",
+            "head -c 200000 /dev/zero | tr '\\0' x\n",
+            "printf '\"}\\n'\n",
+            "cat > /dev/null\n"
+        )).unwrap();
+        fs::set_permissions(&eager, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut context = Context::new();
+        context.add_task("build", Box::new(EmptyTask));
+        ExecutablePlugin::new(&gen).configure(&mut context).unwrap();
+        assert!(context.get_task_deps("build").unwrap().contains("gen:build"));
+        let request : Value = serde_json::from_slice(&fs::read(dir.join("gen.configure.json")).unwrap()).unwrap();
+        assert_eq!(request["method"], "configure");
+        assert_eq!(request["params"]["modules"], Value::Array(vec!()));
+
+        let task = plugin_task(&gen);
+        assert_eq!(task.needs_execution(), false);
+        task.execute().unwrap();
+        let request : Value = serde_json::from_slice(&fs::read(dir.join("gen.execute.json")).unwrap()).unwrap();
+        assert_eq!(request["params"]["task"], "gen:build");
+        assert_eq!(request["params"]["data"], "data");
+
+        let e = plugin_task(&malformed).execute().unwrap_err().to_string();
+        assert!(e.contains("Invalid response"), "{}", e);
+
+        let e = plugin_task(&failing).execute().unwrap_err().to_string();
+        assert!(e.contains("error 42") && e.contains("compiler not found"), "{}", e);
+
+        // The plugin exits with an error for needs_execution, which fails the task instead of executing it.
+        let task = plugin_task(&broken);
+        assert_eq!(task.needs_execution(), true);
+        let e = task.execute().unwrap_err().to_string();
+        assert!(e.contains("needs execution"), "{}", e);
+        assert!(!dir.join("broken.execute.json").exists());
+
+        let mut task = plugin_task(&eager);
+        task.data = Value::from("x".repeat(200000));
+        task.execute().unwrap();
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
\ No newline at end of file
@@ -1,40 +1,88 @@
 use ::Plugin;
 use failure::Error;
+use ::cmake::CMakePlugin;
 use ::conan::ConanPlugin;
+use ::executable::ExecutablePlugin;
+use build::BuildError;
 use std::fs;
+use std::path::Path;
 use std::path::PathBuf;
+use template::TEMPLATES_DIR_NAME;
+use template::Templates;
 
 pub struct Framework {
-    plugin_dir: PathBuf
+    plugin_dir: PathBuf,
+    templates_dir: PathBuf
 }
 
 impl Framework {
-    pub fn new(plugin_dir: &PathBuf) -> Framework {
+    ///
+    /// Loads the executable plugins in `plugin_dir`. The built-in plugins look for templates of build scripts in
+    /// `templates_dir` unless the module or the project overrides them.
+    ///
+    pub fn new(plugin_dir: &PathBuf, templates_dir: &PathBuf) -> Framework {
         Framework {
-            plugin_dir: plugin_dir.clone()
+            plugin_dir: plugin_dir.clone(),
+            templates_dir: templates_dir.clone()
         }
     }
 
     pub fn get_plugins(&self) -> Result<Vec<Box<Plugin>>, Vec<Error>> {
-        let mut ret_val : Vec<Box<Plugin>> = vec!(Box::new(ConanPlugin::new()));
-    
-        /* TODO: Implement ExecutablePlugin
+        // The templates folder of the project is relative to the project root, which tasks run in.
+        let templates = Templates::new(vec!(PathBuf::from(TEMPLATES_DIR_NAME), self.templates_dir.clone()));
+        let mut ret_val : Vec<Box<Plugin>> = vec!(Box::new(ConanPlugin::new(&templates)), Box::new(CMakePlugin::new(&templates)));
+        let mut errors : Vec<Error> = vec!();
+
+        if !self.plugin_dir.exists() {
+            debug!("Plugin directory {} does not exist.", self.plugin_dir.display());
+            return Ok(ret_val);
+        }
+
         let plugins = match fs::read_dir(&self.plugin_dir) {
             Ok(iter) => iter,
             Err(e) => {
-                ret_val.push(Err(Error::IOError(e.to_string())));
-                return Ok(ret_val);
+                return Err(vec!(Error::from(BuildError::IOError(format!("Unable to read plugin directory {}: {}", self.plugin_dir.display(), e)))));
             }
         };
 
+        let mut paths = vec!();
         for plugin in plugins {
             match plugin {
-                Ok(p) => ret_val.push(Ok(Box::new(ExecutablePlugin::new(&p.path())?))),
-                Err(e) => ret_val.push(Err(Error::IOError(e.to_string())))
+                Ok(p) => paths.push(p.path()),
+                Err(e) => errors.push(Error::from(BuildError::IOError(e.to_string())))
             };
         }
-        */
 
-        Ok(ret_val)
+        // Load plugins in a stable order so that their tasks are configured the same way on every run.
+        paths.sort();
+        for path in paths {
+            if is_executable(&path) {
+                debug!("Loading plugin: {}", path.display());
+                ret_val.push(Box::new(ExecutablePlugin::new(&path)));
+            } else {
+                debug!("Ignoring non-executable file in plugin directory: {}", path.display());
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(ret_val)
+        } else {
+            Err(errors)
+        }
     }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    match fs::metadata(path) {
+        Ok(m) => m.is_file() && m.permissions().mode() & 0o111 != 0,
+        Err(_) => false
+    }
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
 }
\ No newline at end of file
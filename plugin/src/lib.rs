@@ -1,19 +1,24 @@
 extern crate build;
-extern crate chrono;
 extern crate dunce;
 extern crate failure;
+extern crate handlebars;
 extern crate serde;
-extern crate timeago;
+extern crate serde_json;
 extern crate os_pipe;
 extern crate walkdir;
 
 #[macro_use] extern crate indoc;
 #[macro_use] extern crate log;
+#[macro_use] extern crate serde_derive;
 
+mod cmake;
+mod common;
 mod conan;
+mod executable;
 mod framework;
 mod path_util;
 mod plugin;
+mod template;
 
 pub use framework::Framework;
 pub use plugin::Plugin;
\ No newline at end of file
@@ -3,6 +3,7 @@ use failure::Error;
 use std::collections::HashMap;
 
 pub trait Plugin {
+    fn name(&self) -> String;
     fn get_documentation(&self) -> HashMap<String, String>;
     fn configure(&self, context: &mut Context) -> Result<(), Error>;
 }
\ No newline at end of file
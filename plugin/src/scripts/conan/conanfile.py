@@ -2,23 +2,19 @@ from conans import ConanFile, CMake
 import os
 import shutil
 
-def get_requirements():
-    L = "${conan.requires}"
-    if (L == None or L == ""):
-        return None
-    return tuple(L.split(","))
-
 class GenericConan(ConanFile):
-    name = "${conan.name}"
-    version = "${conan.version}"
+    name = "{{conan.name}}"
+    version = "{{conan.version}}"
     url = "none"
-    description = "${conan.description}"
+    description = "{{conan.description}}"
     settings = "os", "compiler", "build_type", "arch"
     options = {"shared": [True, False]}
-    default_options = "shared=False"
+    default_options = {{conan.default_options}}
     generators = "cmake"
     exports_sources = ["CMakeLists.txt", "src/*"]
-    requires = get_requirements()
+{{#if conan.requires}}
+    requires = ({{#each conan.requires}}"{{this}}", {{/each}})
+{{/if}}
 
     # We do this in build because "source" is only executed by conan once forever.
     def copy_source_files_from(self, module_path):
@@ -54,7 +50,7 @@ class GenericConan(ConanFile):
         f.close()
 
     def build(self):
-        module_path = "${conan.module_path}"
+        module_path = "{{conan.module_path}}"
 
         print "Module: name=%s, version=%s, license=%s" % (self.name, self.version, self.license)
         print "Module Folder: ", module_path
@@ -63,6 +59,9 @@ class GenericConan(ConanFile):
         print "Requirements:\n", self.requires
 
         self.copy_source_files_from(module_path)
+{{#if (eq conan.artifact_type "header")}}
+        # Header-only libraries are packaged without compiling anything.
+{{else}}
         self.generate_filelist()
 
         cmake = CMake(self)
@@ -73,10 +72,19 @@ class GenericConan(ConanFile):
         # self.run('cmake %s/hello %s'
         #          % (self.source_folder, cmake.command_line))
         # self.run("cmake --build . %s" % cmake.build_config)
+{{/if}}
+
+{{#if (eq conan.artifact_type "header")}}
+    def package_id(self):
+        self.info.header_only()
 
+{{/if}}
     def package_info(self):
-        if "${conan.artifact_type}" == "lib":
-            self.cpp_info.libs=["${conan.artifact_name}"]
+{{#if (eq conan.artifact_type "lib")}}
+        self.cpp_info.libs=["{{conan.artifact_name}}"]
+{{else}}
+        pass
+{{/if}}
 
     def package(self):
         self.copy("*.h", dst="include", src="src")
@@ -0,0 +1,13 @@
+from conans import ConanFile, CMake
+
+class TestConan(ConanFile):
+    name = "{{conan.name}}_test"
+    url = "none"
+    settings = "os", "compiler", "build_type", "arch"
+    generators = "cmake"
+    requires = ({{#each conan.test_requires}}"{{this}}", {{/each}})
+
+    def build(self):
+        cmake = CMake(self)
+        cmake.configure()
+        cmake.build()
@@ -0,0 +1,199 @@
+use build::BuildError;
+use build::ConfigValue;
+use failure::Error;
+use handlebars::Handlebars;
+use handlebars::no_escape;
+use serde_json::Map;
+use serde_json::Value;
+use std::fs;
+use std::iter;
+use std::path::PathBuf;
+
+// The folder of a module or of the project with templates that replace the built-in ones.
+pub const TEMPLATES_DIR_NAME: &'static str = "templates";
+
+///
+/// Finds the templates of build scripts and renders them with [Handlebars](https://handlebarsjs.com/).
+///
+/// A template such as "conan/CMakeLists.txt" is looked up in the "templates" folder of the module, then in each of
+/// the shared template folders in order and finally among the built-in templates.
+///
+#[derive(Debug, Clone)]
+pub struct Templates {
+    dirs: Vec<PathBuf>
+}
+
+impl Templates {
+    pub fn new(dirs: Vec<PathBuf>) -> Templates {
+        Templates {
+            dirs: dirs
+        }
+    }
+
+    ///
+    /// Returns where a template was found and its source.
+    ///
+    pub fn find(&self, module_dir: &PathBuf, name: &str) -> Result<(String, String), Error> {
+        for dir in iter::once(module_dir.join(TEMPLATES_DIR_NAME)).chain(self.dirs.iter().cloned()) {
+            let path = dir.join(name);
+            if path.is_file() {
+                debug!("Using template: {}", path.display());
+                return match fs::read_to_string(&path) {
+                    Ok(source) => Ok((path.display().to_string(), source)),
+                    Err(e) => Err(BuildError::IOError(format!("Unable to read template {}: {}", path.display(), e)))?
+                };
+            }
+        }
+
+        match builtin(name) {
+            Some(source) => Ok((format!("{} (built-in)", name), source.to_string())),
+            None => Err(BuildError::IOError(format!("There is no template {}.", name)))?
+        }
+    }
+
+    pub fn render(&self, module_dir: &PathBuf, name: &str, data: &Value) -> Result<String, Error> {
+        let (path, source) = self.find(module_dir, name)?;
+        render(&path, &source, data)
+    }
+}
+
+fn builtin(name: &str) -> Option<&'static str> {
+    match name {
+        "conan/conanfile.py" => Some(include_str!("scripts/conan/conanfile.py")),
+        "conan/CMakeLists.txt" => Some(include_str!("scripts/conan/CMakeLists.txt")),
+        "conan/test/conanfile.py" => Some(include_str!("scripts/conan/test/conanfile.py")),
+        "conan/test/CMakeLists.txt" => Some(include_str!("scripts/conan/test/CMakeLists.txt")),
+        "cmake/CMakeLists.txt" => Some(include_str!("scripts/cmake/CMakeLists.txt")),
+        _ => None
+    }
+}
+
+///
+/// Renders a template. Referring to a value that is not defined is an error.
+///
+pub fn render(name: &str, template: &str, data: &Value) -> Result<String, Error> {
+    let mut handlebars = Handlebars::new();
+    // Build scripts are not HTML.
+    handlebars.register_escape_fn(no_escape);
+    handlebars.set_strict_mode(true);
+
+    match handlebars.render_template(template, data) {
+        Ok(rendered) => Ok(rendered),
+        Err(e) => Err(BuildError::ConfigError(format!("Unable to render template {}: {}", name, e)))?
+    }
+}
+
+pub fn to_json(value: &ConfigValue) -> Value {
+    match value {
+        ConfigValue::String(s) => Value::from(s.clone()),
+        ConfigValue::Integer(n) => Value::from(*n),
+        ConfigValue::Float(n) => Value::from(*n),
+        ConfigValue::Bool(b) => Value::from(*b),
+        ConfigValue::List(l) => Value::Array(l.iter().map(to_json).collect()),
+        ConfigValue::Map(m) => Value::Object(m.iter().map(|(k, v)| (k.clone(), to_json(v))).collect())
+    }
+}
+
+///
+/// The template data of a config. Keys containing dots become nested maps so that a value written as
+/// `conan.version: 0.1` or as `conan: {version: 0.1}` is referred to as `{{conan.version}}` either way.
+///
+pub fn to_data(value: &ConfigValue) -> Value {
+    match value {
+        ConfigValue::Map(m) => {
+            let mut data = Value::Object(Map::new());
+            for (k, v) in m {
+                merge(node(&mut data, k), to_data(v));
+            }
+            data
+        },
+        ConfigValue::List(l) => Value::Array(l.iter().map(to_data).collect()),
+        v => to_json(v)
+    }
+}
+
+///
+/// Sets the value of a dotted key such as "conan.name", replacing whatever is in the way.
+///
+pub fn set(data: &mut Value, key: &str, value: Value) {
+    *node(data, key) = value;
+}
+
+fn node<'a>(data: &'a mut Value, key: &str) -> &'a mut Value {
+    let mut node = data;
+    for part in key.split('.') {
+        if !node.is_object() {
+            *node = Value::Object(Map::new());
+        }
+        node = node.as_object_mut().unwrap().entry(part.to_string()).or_insert(Value::Null);
+    }
+    node
+}
+
+fn merge(node: &mut Value, value: Value) {
+    match (node, value) {
+        (Value::Object(node), Value::Object(value)) => {
+            for (k, v) in value {
+                merge(node.entry(k).or_insert(Value::Null), v);
+            }
+        },
+        (node, value) => *node = value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn rendering() {
+        let mut data = Value::Null;
+        set(&mut data, "conan.name", Value::from("hello"));
+        set(&mut data, "conan.requires", Value::from(vec!("a/1@u/c", "b/2@u/c")));
+
+        assert_eq!(
+            render("t", "project({{conan.name}} ${CMAKE_BINARY_DIR})", &data).unwrap(),
+            "project(hello ${CMAKE_BINARY_DIR})"
+        );
+        assert_eq!(
+            render("t", "{{#each conan.requires}}\"{{this}}\", {{/each}}", &data).unwrap(),
+            "\"a/1@u/c\", \"b/2@u/c\", "
+        );
+        assert_eq!(render("t", "{{#if (eq conan.name \"hello\")}}lib{{else}}bin{{/if}}", &data).unwrap(), "lib");
+        assert!(render("t", "{{conan.version}}", &data).is_err());
+
+        let mut options = BTreeMap::new();
+        options.insert("shared".to_string(), ConfigValue::Bool(true));
+        let mut config = BTreeMap::new();
+        config.insert("conan.version".to_string(), ConfigValue::String("0.1".to_string()));
+        config.insert("conan".to_string(), ConfigValue::Map(options));
+        let data = to_data(&ConfigValue::Map(config));
+        assert_eq!(render("t", "{{conan.version}} {{conan.shared}}", &data).unwrap(), "0.1 true");
+    }
+
+    #[test]
+    fn lookup() {
+        let root = env::temp_dir().join(format!("mib-template-test-{}", process::id()));
+        let module_dir = root.join("module");
+        let shared_dir = root.join("shared");
+        for dir in &[module_dir.join(TEMPLATES_DIR_NAME).join("cmake"), shared_dir.join("cmake"), shared_dir.join("conan")] {
+            fs::create_dir_all(dir).unwrap();
+        }
+        fs::write(module_dir.join(TEMPLATES_DIR_NAME).join("cmake/CMakeLists.txt"), "module").unwrap();
+        fs::write(shared_dir.join("cmake/CMakeLists.txt"), "shared").unwrap();
+        fs::write(shared_dir.join("conan/CMakeLists.txt"), "shared").unwrap();
+
+        let templates = Templates::new(vec!(shared_dir));
+        let source = |name| templates.find(&module_dir, name).unwrap().1;
+        assert_eq!(source("cmake/CMakeLists.txt"), "module");
+        assert_eq!(source("conan/CMakeLists.txt"), "shared");
+        assert_eq!(source("conan/conanfile.py"), include_str!("scripts/conan/conanfile.py"));
+        assert!(templates.find(&module_dir, "conan/missing.txt").is_err());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
\ No newline at end of file